@@ -1,13 +1,19 @@
-use std::fs::{read_dir};
-use std::path::Path;
+use std::fs::{read_dir, remove_file};
+use std::path::{Path, PathBuf};
 use std::env;
-use crate::database::{table::Table};
+use crate::ast::{Statement, TriggerTiming};
+use crate::database::{DBColumn, DBField, DataTypes, errors::DBError, table::{DBResult, Table, TABLE_EXTENSION}, trigger::{Trigger, TRIGGER_EXTENSION}, view::{View, MATERIALIZED_VIEW_EXTENSION, VIEW_EXTENSION}};
+use crate::sql::{engine::{Cursor, Engine, QueryResult}, errors::ParseError, functions::FunctionRegistry, parser::parse_sql};
 
 /* Datebase is the struct holding tables. */
 
 #[derive(Debug)]
 pub struct DB {
+    dir: PathBuf,
     tables: Vec<Table>,
+    views: Vec<View>,
+    triggers: Vec<Trigger>,
+    functions: FunctionRegistry,
 }
 
 impl DB {
@@ -16,7 +22,7 @@ impl DB {
         let dir_itr = read_dir(path)?;
         env::set_current_dir(path)?;
         dbg!(env::current_dir()?);
-        let mut db: DB = DB {tables: vec![]};
+        let mut db: DB = DB {dir: env::current_dir()?, tables: vec![], views: vec![], triggers: vec![], functions: FunctionRegistry::default()};
         for entry in dir_itr {
             let entry = entry?;
             if entry.metadata()?.is_dir() {continue;}
@@ -24,8 +30,17 @@ impl DB {
                 Ok(s) => s.to_string(),
                 Err(_) => continue,
             };
+            if Path::new(&string).extension().is_some_and(|e| e == VIEW_EXTENSION || e == MATERIALIZED_VIEW_EXTENSION) {
+                db.views.push(View::load(&entry.path())?);
+                continue;
+            }
+            if Path::new(&string).extension().is_some_and(|e| e == TRIGGER_EXTENSION) {
+                db.triggers.push(Trigger::load(&entry.path())?);
+                continue;
+            }
             db.tables.push(Table::new(&string)?);
         };
+        db.flag_triggered_tables();
         Ok(db)        
     }
     pub fn get_mut_table(&mut self, table_name: &str) -> Option<&mut Table> {
@@ -34,9 +49,228 @@ impl DB {
             }
             None
     }
+    pub fn get_table(&self, table_name: &str) -> Option<&Table> {
+            self.tables.iter().find(|tb| tb.name == table_name)
+    }
     pub fn get_table_count(&self) -> usize {
         self.tables.len()
     }
+    /// Makes 'f' callable from sql as 'name(...)', names being case insensitive.
+    /// Its results are typed 'returns', a value of another type failing the call.
+    pub fn register_scalar_function(
+        &mut self,
+        name: &str,
+        returns: DataTypes,
+        f: impl Fn(&[DBField]) -> Result<DBField, String> + Send + Sync + 'static,
+    ) {
+        self.functions.register_scalar(name, returns, f);
+    }
+    /// Makes an aggregate callable from sql as 'name(...)'. For each query the
+    /// state starts as 'init()', 'step' is fed the arguments of every row and
+    /// 'finalize' turns the state into the result, typed 'returns'.
+    pub fn register_aggregate_function<S: 'static>(
+        &mut self,
+        name: &str,
+        returns: DataTypes,
+        init: impl Fn() -> S + Send + Sync + 'static,
+        step: impl Fn(&mut S, &[DBField]) -> Result<(), String> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<DBField, String> + Send + Sync + 'static,
+    ) {
+        self.functions.register_aggregate(name, returns, init, step, finalize);
+    }
+    pub fn functions(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+    /// Lexes, parses and runs a statment against the database
+    ///
+    /// # Errors
+    ///
+    /// ParseError if the sql is not a single valid statment, otherwise same
+    /// as 'Engine::run_on_db'
+    pub fn execute(&mut self, sql_s: &str) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ast_root = parse_sql(sql_s).map_err(ParseError)?;
+        Engine::new(ast_root).run_on_db(self)
+    }
+    /// Same as 'execute' for the statments only reading rows, a select,
+    /// compound or not, or a WITH
+    ///
+    /// # Errors
+    ///
+    /// ParseError if the sql is not a single valid statment, otherwise same
+    /// as 'Engine::run_read_only'
+    pub fn query(&self, sql_s: &str) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ast_root = parse_sql(sql_s).map_err(ParseError)?;
+        Engine::new(ast_root).run_read_only(self)
+    }
+    /// Same as 'query', the rows being handed out one at a time by the cursor
+    /// so they need not all be held at once, see 'Engine::run_iter'
+    ///
+    /// # Errors
+    ///
+    /// ParseError if the sql is not a single valid statment, otherwise same
+    /// as 'Engine::run_iter'
+    pub fn query_iter(&self, sql_s: &str) -> Result<Cursor<'_>, Box<dyn std::error::Error>> {
+        let ast_root = parse_sql(sql_s).map_err(ParseError)?;
+        Engine::new(ast_root).run_iter(self)
+    }
+    pub fn get_view(&self, view_name: &str) -> Option<&View> {
+        self.views.iter().find(|v| v.name == view_name)
+    }
+    /// Stores 'query' as the view 'name', its definition file being written
+    /// into the database directory
+    ///
+    /// # Errors
+    ///
+    /// DBError if a table or view already goes by that name or the file can
+    /// not be written
+    pub fn create_view(&mut self, name: &str, query: Statement) -> DBResult<()> {
+        if self.get_table(name).is_some() || self.get_view(name).is_some() {
+            return Err(DBError::NameInUse(name.to_string()));
+        }
+        let view = View {name: name.to_string(), query, materialized: false};
+        view.save(&self.dir)?;
+        self.views.push(view);
+        Ok(())
+    }
+    /// Stores 'query' as the materialized view 'name', its current result
+    /// 'rows' going into a new table of the same name
+    ///
+    /// # Errors
+    ///
+    /// DBError if a table or view already goes by that name, the rows do not
+    /// fit the header or the files can not be written
+    pub fn create_materialized_view(
+        &mut self,
+        name: &str,
+        query: Statement,
+        header: Vec<DBColumn>,
+        rows: Vec<Vec<DBField>>,
+    ) -> DBResult<()> {
+        if self.get_table(name).is_some() || self.get_view(name).is_some() {
+            return Err(DBError::NameInUse(name.to_string()));
+        }
+        let mut table = Table::create(&self.dir, name, header.clone())?;
+        let view = View {name: name.to_string(), query, materialized: true};
+        if let Err(e) = table.replace_rows(header, rows).and_then(|_| view.save(&self.dir)) {
+            let _ = remove_file(self.table_path(name));
+            return Err(e);
+        }
+        self.tables.push(table);
+        self.views.push(view);
+        Ok(())
+    }
+    /// Removes the view along with its definition file, and the table holding
+    /// the rows of a materialized one
+    ///
+    /// # Errors
+    ///
+    /// DBError if there is no such view or the files can not be removed
+    pub fn drop_view(&mut self, name: &str) -> DBResult<()> {
+        let Some(pos) = self.views.iter().position(|v| v.name == name) else {
+            return Err(DBError::ViewNotFound(name.to_string()));
+        };
+        if let Err(e) = remove_file(self.views[pos].path(&self.dir)) {
+            return Err(DBError::FileError(Box::new(e)));
+        }
+        if self.views.remove(pos).materialized {
+            self.tables.retain(|tb| tb.name != name);
+            if let Err(e) = remove_file(self.table_path(name)) {
+                return Err(DBError::FileError(Box::new(e)));
+            }
+        }
+        Ok(())
+    }
+    /// The rows of the named tables, for 'restore_tables' to put back if a
+    /// statment fails half way
+    pub fn snapshot_tables(&self, table_names: &[String]) -> Vec<(String, Vec<Vec<DBField>>)> {
+        self.tables.iter()
+            .filter(|tb| table_names.contains(&tb.name))
+            .map(|tb| (tb.name.clone(), tb.snapshot()))
+            .collect()
+    }
+    /// Puts back the rows taken by 'snapshot_tables', rewriting the files of
+    /// the tables that changed since
+    ///
+    /// # Errors
+    ///
+    /// DBError if a file can not be written
+    pub fn restore_tables(&mut self, snapshot: Vec<(String, Vec<Vec<DBField>>)>) -> DBResult<()> {
+        for (name, entries) in snapshot {
+            if let Some(tb) = self.get_mut_table(&name) {
+                tb.restore(entries)?;
+            }
+        }
+        Ok(())
+    }
+    /// The tables an insert into 'table_name' may write to, the table itself
+    /// and those the bodies of its triggers insert into, transitively
+    pub fn trigger_targets(&self, table_name: &str) -> Vec<String> {
+        let mut targets = vec![table_name.to_string()];
+        let mut idx = 0;
+        while idx < targets.len() {
+            let table_name = targets[idx].clone();
+            for t in self.triggers.iter().filter(|t| t.definition.table == table_name) {
+                if let Statement::Insert(is) = &*t.definition.body && !targets.contains(&is.table) {
+                    targets.push(is.table.clone());
+                }
+            }
+            idx += 1;
+        }
+        targets
+    }
+    /// Marks the tables set off by a trigger, their rows being refused by
+    /// 'Table::insert_many' which can not run it
+    fn flag_triggered_tables(&mut self) {
+        for tb in self.tables.iter_mut() {
+            tb.set_has_triggers(self.triggers.iter().any(|t| t.definition.table == tb.name));
+        }
+    }
+    /// The triggers set off by inserting into 'table_name' at the given time,
+    /// in the order they were created
+    pub fn triggers_on(&self, table_name: &str, timing: TriggerTiming) -> Vec<&Trigger> {
+        self.triggers.iter()
+            .filter(|t| t.definition.table == table_name && t.definition.timing == timing)
+            .collect()
+    }
+    pub fn get_trigger(&self, trigger_name: &str) -> Option<&Trigger> {
+        self.triggers.iter().find(|t| t.name() == trigger_name)
+    }
+    /// Stores the trigger, its definition file being written into the
+    /// database directory
+    ///
+    /// # Errors
+    ///
+    /// DBError if a trigger already goes by that name or the file can not be
+    /// written
+    pub fn create_trigger(&mut self, trigger: Trigger) -> DBResult<()> {
+        if self.get_trigger(trigger.name()).is_some() {
+            return Err(DBError::TriggerExists(trigger.name().to_string()));
+        }
+        trigger.save(&self.dir)?;
+        self.triggers.push(trigger);
+        self.flag_triggered_tables();
+        Ok(())
+    }
+    /// Removes the trigger along with its definition file
+    ///
+    /// # Errors
+    ///
+    /// DBError if there is no such trigger or the file can not be removed
+    pub fn drop_trigger(&mut self, name: &str) -> DBResult<()> {
+        let Some(pos) = self.triggers.iter().position(|t| t.name() == name) else {
+            return Err(DBError::TriggerNotFound(name.to_string()));
+        };
+        if let Err(e) = remove_file(self.triggers[pos].path(&self.dir)) {
+            return Err(DBError::FileError(Box::new(e)));
+        }
+        self.triggers.remove(pos);
+        self.flag_triggered_tables();
+        Ok(())
+    }
+    /// Where the database puts the file of the table it creates as 'name'
+    fn table_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, TABLE_EXTENSION))
+    }
 }
 
 
@@ -2,14 +2,30 @@ use std::error::Error;
 use std::fmt;
 
 use crate::database::{DBField, DataTypes};
+use crate::sql::errors::EngineError;
 
 #[derive(Debug)]
 pub enum DBError {
     ColumnNotFound(Vec<String>),
     FileError(Box<dyn std::error::Error>),
     GenericLoadingError,
-    MalformedInsertInput,
     MistypedInsertInput(DBField, DataTypes),
+    InsertArityMismatch(usize, usize),
+    UniqueViolation(String, DBField),
+    NoConflictTarget,
+    InvalidExpression(EngineError),
+    NameInUse(String),
+    ViewNotFound(String),
+    InvalidViewDefinition(String, String),
+    TriggerExists(String),
+    TriggerNotFound(String),
+    InvalidTriggerDefinition(String, String),
+    TriggersNotRun(String),
+    RollbackFailed(Box<dyn std::error::Error>, Box<DBError>),
+    InvalidColumnDefinition(String, String),
+    InvalidGeneratedColumn(String, String),
+    GeneratedColumnWrite(String),
+    GeneratedColumnType(String, DBField, DataTypes),
     _InvalidComparasion,
 }
 impl fmt::Display for DBError {
@@ -26,20 +42,42 @@ impl fmt::Display for DBError {
             },
             DBError::FileError(e) => {
                 let mut out_s = "error occured with the db file. Reported error: ".to_string();
-                out_s.push_str(&*e.to_string());
+                out_s.push_str(&e.to_string());
                 out_s
             },
-            DBError::MalformedInsertInput => "malformed insert input, missing field specified to be inserted".to_string(),
             DBError::MistypedInsertInput(f, exp_type) => {
                 let mut out_s = "wrong input type. Got ".to_string();
                     out_s.push_str(match f {
                         DBField::Text(_) => "TEXT",
                         DBField::Int(_) => "INT",
+                        DBField::Null => "NULL",
                     });
                     out_s.push_str(" expected ");
                     out_s.push_str(&exp_type.to_file_string());
                     out_s
             },
+        DBError::InsertArityMismatch(expected, got) => {
+            format!("wrong number of values to insert. Got {} expected {}", got, expected)
+        },
+        DBError::UniqueViolation(col, f) => {
+            format!("duplicate value '{}' in unique column '{}'", f.to_file_string(), col)
+        },
+        DBError::NoConflictTarget => "no conflict column given and the table declares no unique column".to_string(),
+        DBError::InvalidExpression(e) => format!("failed to evaluate an expression: {}", e),
+        DBError::NameInUse(name) => format!("a table or view named '{}' already exists", name),
+        DBError::ViewNotFound(name) => format!("view '{}' not found", name),
+        DBError::InvalidViewDefinition(name, e) => format!("the definition of view '{}' is invalid: {}", name, e),
+        DBError::TriggerExists(name) => format!("a trigger named '{}' already exists", name),
+        DBError::TriggerNotFound(name) => format!("trigger '{}' not found", name),
+        DBError::InvalidTriggerDefinition(name, e) => format!("the definition of trigger '{}' is invalid: {}", name, e),
+        DBError::TriggersNotRun(name) => format!("table '{}' has triggers, insert into it through the database for them to run", name),
+        DBError::RollbackFailed(e, restore) => format!("{}, undoing its writes failed as well: {}", e, restore),
+        DBError::InvalidColumnDefinition(name, e) => format!("the definition of column '{}' is invalid: {}", name, e),
+        DBError::InvalidGeneratedColumn(name, e) => format!("generated column '{}' is invalid: {}", name, e),
+        DBError::GeneratedColumnWrite(name) => format!("can not write to generated column '{}'", name),
+        DBError::GeneratedColumnType(name, f, exp_type) => {
+            format!("generated column '{}' computed '{}', expected {}", name, f.to_file_string(), exp_type.to_file_string())
+        },
         DBError::_InvalidComparasion => "Invalid comparsion was made".to_string(),
         DBError::GenericLoadingError => "Error loading the db".to_string(),
         })
@@ -1,16 +1,21 @@
 pub mod db;
 pub mod errors;
 pub mod table;
+pub mod trigger;
+pub mod view;
 
+use crate::ast::Expr;
 
 /*  ## Database ##
  *  Module holding all the constructs relating to the 'physical' database
  */
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DBField {
     Text(String),
     Int(i32),
+    /// produced while evaluating expressions, only stored in NULL columns
+    Null,
 }
 
 impl DBField {
@@ -18,6 +23,7 @@ impl DBField {
         match self {
             DBField::Text(s) => s.clone(),
             DBField::Int(i) => i.to_string(),
+            DBField::Null => "NULL".to_string(),
         }
     }
 }
@@ -26,16 +32,93 @@ impl DBField {
 pub struct DBColumn {
     dt_type: DataTypes,
     pub name: String,
+    constraint: Option<ColumnConstraint>,
+    /// whether the column may hold NULL, as those of materialized views
+    nullable: bool,
+    generated: Option<GeneratedColumn>,
 }
 
 impl DBColumn {
+    pub fn new(name: &str, dt_type: DataTypes) -> Self {
+        DBColumn {dt_type, name: name.to_string(), constraint: None, nullable: false, generated: None}
+    }
+    /// A column that may hold NULL, as those of materialized views
+    pub fn nullable(name: &str, dt_type: DataTypes) -> Self {
+        DBColumn {nullable: true, ..DBColumn::new(name, dt_type)}
+    }
     fn to_file_string(&self) -> String {
         let mut out_str = "".to_string();
         out_str.push_str(&self.name);
         out_str.push_str(": ");
         out_str.push_str(&self.dt_type.to_file_string());
+        if self.nullable {
+            out_str.push_str(" NULL");
+        }
+        if let Some(c) = &self.constraint {
+            out_str.push(' ');
+            out_str.push_str(&c.to_file_string());
+        }
+        if let Some(g) = &self.generated {
+            out_str.push(' ');
+            out_str.push_str(&g.to_file_string());
+        }
         out_str
     }
+    pub fn data_type(&self) -> &DataTypes {
+        &self.dt_type
+    }
+    pub fn is_unique(&self) -> bool {
+        matches!(self.constraint, Some(ColumnConstraint::Unique | ColumnConstraint::PrimaryKey))
+    }
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+    /// Whether the column may hold 'field', one of its type or a NULL if it is nullable
+    pub fn accepts(&self, field: &DBField) -> bool {
+        self.dt_type.accepts(field) || (*field == DBField::Null && self.is_nullable())
+    }
+    pub fn generated(&self) -> Option<&GeneratedColumn> {
+        self.generated.as_ref()
+    }
+    /// Whether the values of the column are computed on every read rather than stored
+    pub fn is_virtual(&self) -> bool {
+        self.generated.as_ref().is_some_and(|g| !g.stored)
+    }
+}
+
+/* A column computed from the other columns of its row, declared after the type
+ * as 'GENERATED ALWAYS AS (expr) [STORED|VIRTUAL]'. Stored values are computed
+ * on insert and written to the file, virtual ones, the default, are left out of
+ * the file and computed whenever the row is read.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedColumn {
+    pub expr: Expr,
+    pub stored: bool,
+}
+
+impl GeneratedColumn {
+    pub fn to_file_string(&self) -> String {
+        format!("GENERATED ALWAYS AS ({}) {}", self.expr, if self.stored {"STORED"} else {"VIRTUAL"})
+    }
+}
+
+/* Constraints declared after the type in the header
+ * e.g. 'id: INT PRIMARY KEY', both kinds forbid duplicate values in the column
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraint {
+    Unique,
+    PrimaryKey,
+}
+
+impl ColumnConstraint {
+    pub fn to_file_string(&self) -> String {
+        match self {
+            ColumnConstraint::Unique => "UNIQUE".to_string(),
+            ColumnConstraint::PrimaryKey => "PRIMARY KEY".to_string(),
+        }
+    }
 }
 
 /* Data primitives for the tables
@@ -49,6 +132,12 @@ pub enum DataTypes {
 }
 
 impl DataTypes {
+    pub fn accepts(&self, field: &DBField) -> bool {
+        matches!(
+            (self, field),
+            (DataTypes::TEXT, DBField::Text(_)) | (DataTypes::INT, DBField::Int(_))
+        )
+    }
     pub fn to_file_string(&self) -> String {
         match self {
             DataTypes::TEXT => "TEXT".to_string(),
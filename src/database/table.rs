@@ -1,75 +1,404 @@
+use std::borrow::{Borrow, Cow};
 use std::fs::File;
 use std::path::Path;
 use std::io::{Seek, SeekFrom, Read, Write};
 use itertools::Itertools;
+use std::collections::HashSet;
 use crate::database::{DBColumn, DBField, DataTypes};
-use crate::ast::Expr;
-use crate::engine::Engine;
+use crate::ast::{ColumnDef, ConflictAction, Expr, OnConflict};
+use crate::engine::{Engine, Evaluator};
+use crate::functions::FunctionRegistry;
+use crate::parser::parse_column_sql;
 use crate::database::errors::DBError;
 
 pub type DBResult<T> = Result<T, DBError>;
 
+/// Extension of the table files created by the database itself
+pub const TABLE_EXTENSION: &str = "txt";
+
 #[derive(Debug)]
 pub struct Table {
     pub name: String,
-    file: File,
+    /// None for the temporary tables living only in memory
+    file: Option<File>,
     header: Vec<DBColumn>,
     entries: Vec<Vec<DBField>>,
+    /// set by the database when inserting into the table sets off triggers,
+    /// which only it can run
+    has_triggers: bool,
 }
 
 impl Table {
-    pub fn insert(&mut self, col_names: Option<Vec<&str>>, mut row: Vec<DBField>) -> DBResult<()> {
-        let col_names = match col_names {
-            Some(c) => c,
-            _ => self.header.iter().map(|c| c.name.as_str()).collect(),
+    /// Inserts a row, its generated columns computed with the built-in functions
+    ///
+    /// # Errors
+    ///
+    /// Same as 'insert_many'
+    pub fn insert(&mut self, col_names: Option<Vec<&str>>, row: Vec<DBField>) -> DBResult<()> {
+        self.insert_many(col_names, vec![row], &FunctionRegistry::default())?;
+        Ok(())
+    }
+
+    /// Maps column names onto their positions in the header, keeping the given order
+    ///
+    /// # Errors
+    ///
+    /// DBError::ColumnNotFound if any of the names is not in the header
+    pub fn column_indices(&self, col_names: &[&str]) -> DBResult<Vec<usize>> {
+        let mut col_idx = vec![];
+        for col_nm in col_names {
+            match self.header.iter().position(|col| *col_nm == col.name) {
+                Some(head_idx) => col_idx.push(head_idx),
+                None => return Err(DBError::ColumnNotFound(col_names.iter().map(|cn| cn.to_string()).collect())),
+            }
+        }
+        Ok(col_idx)
+    }
+
+    /// Inserts several rows at once
+    ///
+    /// Every row is type-checked and filled in with defaults before any of them
+    /// is added, so either all rows land in the table or none do. The file is
+    /// rewritten once at the end. Returns the rows as they were stored, defaults
+    /// and generated columns filled in. Without 'col_names' the rows hold a value
+    /// per column that is not generated. The generated columns may call any of
+    /// 'functions'.
+    ///
+    /// # Errors
+    ///
+    /// DBError on a missing column, a type mismatch or when a row does not
+    /// hold exactly one value per column (per header column if 'col_names' is None).
+    /// DBError::TriggersNotRun if the table has triggers, the insert having to
+    /// go through the database for them to run.
+    pub fn insert_many(&mut self, col_names: Option<Vec<&str>>, rows: Vec<Vec<DBField>>, functions: &FunctionRegistry) -> DBResult<Vec<Vec<DBField>>> {
+        self.check_no_triggers()?;
+        self.store_many(col_names, rows, functions)
+    }
+
+    /// Same as 'insert_many', for the engine running the triggers itself
+    pub(crate) fn store_many(&mut self, col_names: Option<Vec<&str>>, rows: Vec<Vec<DBField>>, functions: &FunctionRegistry) -> DBResult<Vec<Vec<DBField>>> {
+        let eval = Evaluator::new(functions.clone());
+        let full_rows = self.build_stored_rows(col_names, rows, &eval)?;
+        self.check_unique(self.entries.iter().chain(full_rows.iter()))?;
+
+        self.entries.extend(full_rows.iter().cloned());
+        if let Err(e) = self.write_to_file() {
+            return Err(DBError::FileError(Box::new(e)));
+        }
+        let mut stored = Vec::with_capacity(full_rows.len());
+        for row in &full_rows {
+            stored.push(self.with_virtual(row, &eval)?.into_owned());
+        }
+        Ok(stored)
+    }
+
+    /// Type-checks the rows and fills them in with defaults the way 'insert_many'
+    /// does, without adding them to the table
+    ///
+    /// # Errors
+    ///
+    /// Same as 'insert_many', except for the unique checks
+    pub fn build_rows(&self, col_names: Option<Vec<&str>>, rows: Vec<Vec<DBField>>, functions: &FunctionRegistry) -> DBResult<Vec<Vec<DBField>>> {
+        let eval = Evaluator::new(functions.clone());
+        let mut full_rows = self.build_stored_rows(col_names, rows, &eval)?;
+        for row in &mut full_rows {
+            self.generate(row, true, &eval)?;
+        }
+        Ok(full_rows)
+    }
+
+    /// Same as 'build_rows', the virtual columns being left NULL as they are kept
+    fn build_stored_rows(&self, col_names: Option<Vec<&str>>, rows: Vec<Vec<DBField>>, eval: &Evaluator) -> DBResult<Vec<Vec<DBField>>> {
+        let col_idx = match col_names {
+            Some(c) => self.column_indices(&c)?,
+            None => self.writable_columns(),
         };
-        for (idx, col_nm) in col_names.iter().enumerate() {
-            let mut found = false;
-            let mut head_idx_to_use = 0;
-            for (head_idx, col) in self.header.iter().enumerate() {
-                if *col_nm == col.name {
-                    found = true; 
-                    head_idx_to_use = head_idx;
+        let mut full_rows = Vec::with_capacity(rows.len());
+        for row in rows {
+            full_rows.push(self.build_row(&col_idx, row, eval)?);
+        }
+        Ok(full_rows)
+    }
+
+    /// Inserts several rows, resolving clashes on the conflict column according
+    /// to 'on_conflict' instead of failing
+    ///
+    /// The conflict column is the explicit target if given, otherwise the first
+    /// UNIQUE / PRIMARY KEY column of the header. Rows are applied in order, so a
+    /// later row may also conflict with an earlier one of the same batch.
+    /// 'DO UPDATE SET' expressions are evaluated by the engine against the
+    /// existing row, the proposed values being reachable as 'excluded.<col>'.
+    /// Returns the inserted and updated rows as stored, skipped ones are left out.
+    ///
+    /// # Errors
+    ///
+    /// Same as 'insert_many', additionally DBError if there is no conflict column
+    /// or an update expression fails. Nothing is written on error.
+    pub fn upsert_many(
+        &mut self,
+        col_names: Option<Vec<&str>>,
+        rows: Vec<Vec<DBField>>,
+        on_conflict: &OnConflict,
+        engine: &Engine,
+    ) -> DBResult<Vec<Vec<DBField>>> {
+        self.check_no_triggers()?;
+        self.store_upsert(col_names, rows, on_conflict, engine)
+    }
+
+    /// Same as 'upsert_many', for the engine running the triggers itself
+    pub(crate) fn store_upsert(
+        &mut self,
+        col_names: Option<Vec<&str>>,
+        rows: Vec<Vec<DBField>>,
+        on_conflict: &OnConflict,
+        engine: &Engine,
+    ) -> DBResult<Vec<Vec<DBField>>> {
+        let eval = engine.evaluator();
+        let col_idx = match col_names {
+            Some(c) => self.column_indices(&c)?,
+            None => self.writable_columns(),
+        };
+        let key_idx = self.conflict_column(on_conflict)?;
+        let mut update_idx = vec![];
+        if let ConflictAction::Update(assignments) = &on_conflict.action {
+            for (col_nm, expr) in assignments {
+                match self.header.iter().position(|col| *col_nm == col.name) {
+                    Some(i) if self.header[i].generated().is_some() => return Err(DBError::GeneratedColumnWrite(col_nm.clone())),
+                    Some(i) => update_idx.push((i, expr)),
+                    None => return Err(DBError::ColumnNotFound(vec![col_nm.clone()])),
                 }
             }
-            if !found {return Err(DBError::ColumnNotFound(col_names.iter().map(|cn| cn.to_string()).collect()))};
+        }
+        // existing columns followed by the proposed ones under the 'excluded' name
+        let mut eval_header = self.header.clone();
+        for col in &self.header {
+            let mut excluded = col.clone();
+            excluded.name = format!("excluded.{}", col.name);
+            eval_header.push(excluded);
+        }
 
-            let field = match row.get(idx) {
-                Some(f) => f,
-                _ => return Err(DBError::MalformedInsertInput),
+        let mut staged = self.entries.clone();
+        let mut affected = vec![];
+        for row in rows {
+            let row = self.build_row(&col_idx, row, eval)?;
+            let existing = staged.iter().position(|e| e.get(key_idx) == Some(&row[key_idx]));
+            let existing = match existing {
+                Some(e) => e,
+                None => {
+                    affected.push(row.clone());
+                    staged.push(row);
+                    continue;
+                }
             };
-            let head_col = &self.header[head_idx_to_use];
-            match field {
-                DBField::Text(_) => {if head_col.dt_type == DataTypes::TEXT {continue;}}
-                DBField::Int(_) => {if head_col.dt_type == DataTypes::INT {continue;}}
+            if update_idx.is_empty() {continue}
+
+            let mut eval_row = self.with_virtual(&staged[existing], eval)?.into_owned();
+            eval_row.extend(self.with_virtual(&row, eval)?.into_owned());
+            let mut updated = staged[existing].clone();
+            for (head_idx, expr) in &update_idx {
+                let field = match eval.eval_value(expr, &eval_row, &eval_header) {
+                    Ok(f) => f,
+                    Err(e) => return Err(DBError::InvalidExpression(e)),
+                };
+                let head_col = &self.header[*head_idx];
+                if !head_col.accepts(&field) {
+                    return Err(DBError::MistypedInsertInput(field, head_col.dt_type.clone()));
+                }
+                updated[*head_idx] = field;
             }
-            return Err(DBError::MistypedInsertInput(field.clone(), head_col.dt_type.clone()))
+            self.generate(&mut updated, false, eval)?;
+            self.clear_virtual(&mut updated);
+            affected.push(updated.clone());
+            staged[existing] = updated;
+        }
+        self.check_unique(staged.iter())?;
+
+        self.entries = staged;
+        if let Err(e) = self.write_to_file() {
+            return Err(DBError::FileError(Box::new(e)));
+        }
+        for row in &mut affected {
+            self.generate(row, true, eval)?;
+        }
+        Ok(affected)
+    }
+
+    /// The position of the column an ON CONFLICT clause checks, its target or
+    /// else the first unique column
+    ///
+    /// # Errors
+    ///
+    /// DBError if the target is missing or virtual, or there is no unique column
+    pub fn has_triggers(&self) -> bool {
+        self.has_triggers
+    }
+
+    pub(crate) fn set_has_triggers(&mut self, has_triggers: bool) {
+        self.has_triggers = has_triggers;
+    }
+
+    /// # Errors
+    ///
+    /// DBError::TriggersNotRun if inserting into the table sets off triggers
+    fn check_no_triggers(&self) -> DBResult<()> {
+        match self.has_triggers {
+            true => Err(DBError::TriggersNotRun(self.name.clone())),
+            false => Ok(()),
         }
+    }
+
+    fn conflict_column(&self, on_conflict: &OnConflict) -> DBResult<usize> {
+        match &on_conflict.target {
+            Some(t) => match self.header.iter().position(|col| col.name == *t) {
+                Some(i) if self.header[i].is_virtual() => {
+                    Err(DBError::InvalidGeneratedColumn(t.clone(), "virtual columns can not be conflict targets".to_string()))
+                }
+                Some(i) => Ok(i),
+                None => Err(DBError::ColumnNotFound(vec![t.clone()])),
+            },
+            None => match self.header.iter().position(|col| col.is_unique()) {
+                Some(i) => Ok(i),
+                None => Err(DBError::NoConflictTarget),
+            },
+        }
+    }
+
+    /// Leaves out of rows laid out by 'build_rows' those 'DO NOTHING' would
+    /// skip, their conflict column matching a stored row or an earlier one of
+    /// the batch. 'DO UPDATE' keeps them all.
+    ///
+    /// # Errors
+    ///
+    /// Same as 'upsert_many' for the conflict column
+    pub fn without_skipped(&self, rows: Vec<Vec<DBField>>, on_conflict: &OnConflict) -> DBResult<Vec<Vec<DBField>>> {
+        let key_idx = self.conflict_column(on_conflict)?;
+        if let ConflictAction::Update(_) = on_conflict.action {
+            return Ok(rows);
+        }
+        let mut seen: HashSet<DBField> = self.entries.iter().filter_map(|e| e.get(key_idx).cloned()).collect();
+        Ok(rows.into_iter().filter(|row| seen.insert(row[key_idx].clone())).collect())
+    }
+
+    /// The rows as stored, for 'restore' to put back
+    pub fn snapshot(&self) -> Vec<Vec<DBField>> {
+        self.entries.clone()
+    }
+
+    /// Puts back the rows taken by 'snapshot', rewriting the file if they
+    /// changed since
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file can not be written
+    pub fn restore(&mut self, entries: Vec<Vec<DBField>>) -> DBResult<()> {
+        if self.entries == entries {
+            return Ok(());
+        }
+        self.entries = entries;
+        self.write_to_file()
+    }
+
+    /// Checks that no UNIQUE / PRIMARY KEY column holds the same value twice
+    fn check_unique<'r>(&self, rows: impl Iterator<Item = &'r Vec<DBField>> + Clone) -> DBResult<()> {
         for (head_idx, col) in self.header.iter().enumerate() {
-            let mut covered = false;
-            for col_nm in &col_names {
-                if *col_nm == col.name {
-                    covered = true;
+            if !col.is_unique() {continue}
+            let mut seen = HashSet::new();
+            for row in rows.clone() {
+                let field = match row.get(head_idx) {
+                    Some(f) => f,
+                    None => continue,
+                };
+                if !seen.insert(field) {
+                    return Err(DBError::UniqueViolation(col.name.clone(), field.clone()));
                 }
             }
-            if covered {continue}
-            let field_to_add = match col.dt_type {
-                DataTypes::TEXT => DBField::Text("".to_string()),
-                DataTypes::INT => DBField::Int(0), 
+        }
+        Ok(())
+    }
+
+    /// Lays out the given fields in header order, checking their types and
+    /// filling the columns not covered by 'col_idx' with default values, the
+    /// stored generated columns with their computed ones
+    fn build_row(&self, col_idx: &[usize], row: Vec<DBField>, eval: &Evaluator) -> DBResult<Vec<DBField>> {
+        if row.len() != col_idx.len() {
+            return Err(DBError::InsertArityMismatch(col_idx.len(), row.len()));
+        }
+        let mut out_row: Vec<DBField> = self.header.iter().map(|col| match col.dt_type {
+            _ if col.generated.is_some() => DBField::Null,
+            DataTypes::TEXT => DBField::Text("".to_string()),
+            DataTypes::INT => DBField::Int(0),
+        }).collect();
+        for (field, head_idx) in row.into_iter().zip(col_idx) {
+            let head_col = &self.header[*head_idx];
+            if head_col.generated.is_some() {
+                return Err(DBError::GeneratedColumnWrite(head_col.name.clone()));
+            }
+            if !head_col.accepts(&field) {
+                return Err(DBError::MistypedInsertInput(field, head_col.dt_type.clone()));
+            }
+            out_row[*head_idx] = field;
+        }
+        // the virtual columns are computed too, stored ones may be reading them
+        self.generate(&mut out_row, false, eval)?;
+        self.clear_virtual(&mut out_row);
+        Ok(out_row)
+    }
+
+    /// The columns an insert without a column list gives values for, those
+    /// that are not generated
+    fn writable_columns(&self) -> Vec<usize> {
+        (0..self.header.len()).filter(|idx| self.header[*idx].generated.is_none()).collect()
+    }
+
+    /// Computes the generated columns of the row in header order, all of them
+    /// or only the virtual ones, with the functions known to 'eval'
+    ///
+    /// # Errors
+    ///
+    /// DBError if an expression fails or gives a value of the wrong type
+    fn generate(&self, row: &mut [DBField], virtual_only: bool, eval: &Evaluator) -> DBResult<()> {
+        for (idx, col) in self.header.iter().enumerate() {
+            let Some(g) = &col.generated else {continue};
+            if virtual_only && g.stored {continue}
+            let field = match eval.eval_value(&g.expr, row, &self.header) {
+                Ok(f) => f,
+                Err(e) => return Err(DBError::InvalidExpression(e)),
             };
-            row.insert(head_idx, field_to_add);
+            if !col.dt_type.accepts(&field) {
+                return Err(DBError::GeneratedColumnType(col.name.clone(), field, col.dt_type.clone()));
+            }
+            row[idx] = field;
         }
+        Ok(())
+    }
+
+    /// Blanks out the virtual columns, they are not kept with the row
+    fn clear_virtual(&self, row: &mut [DBField]) {
+        for (field, col) in row.iter_mut().zip(&self.header) {
+            if col.is_virtual() {
+                *field = DBField::Null;
+            }
+        }
+    }
 
-        self.entries.push(row);
-        match self.write_to_file() {
-            Ok(_) => Ok(()),
-            Err(e) => Err(DBError::FileError(Box::new(e))),
+    /// The row as it is read, its virtual columns computed
+    ///
+    /// # Errors
+    ///
+    /// Same as 'generate'
+    fn with_virtual<'r>(&self, row: &'r [DBField], eval: &Evaluator) -> DBResult<Cow<'r, [DBField]>> {
+        if !self.header.iter().any(|col| col.is_virtual()) || row.len() < self.header.len() {
+            return Ok(Cow::Borrowed(row));
         }
+        let mut row = row.to_vec();
+        self.generate(&mut row, true, eval)?;
+        Ok(Cow::Owned(row))
     }
     pub fn select_all_cols(&self) -> DBResult<Vec<Vec<DBField>>> {
-        let mut out_vec = vec![vec![]];
+        let eval = Evaluator::new(FunctionRegistry::default());
+        let mut out_vec = vec![];
         for row in &self.entries {
-            out_vec.push(row.clone());
+            out_vec.push(self.with_virtual(row, &eval)?.into_owned());
         }
         Ok(out_vec)
     }
@@ -91,9 +420,11 @@ impl Table {
         for i in &col_idx {
             if *i > max_i {max_i = *i}
         }
-        let mut out_vec = vec![vec![]];
+        let eval = Evaluator::new(FunctionRegistry::default());
+        let mut out_vec = vec![];
         for row in &self.entries {
             if row.len() < max_i + 1 {continue;}
+            let row = self.with_virtual(row, &eval)?;
             let mut row_vec = vec![];
             for idx in &col_idx {
                 row_vec.push(row[*idx].clone());
@@ -102,12 +433,103 @@ impl Table {
         }
         Ok(out_vec)
     }
+    pub fn header(&self) -> &[DBColumn] {
+        &self.header
+    }
+    pub fn row_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The rows satisfying all of 'where_exprs', as read, so with their virtual
+    /// columns computed
+    ///
+    /// # Errors
+    ///
+    /// DBError::InvalidExpression if the engine fails on any of the rows
+    pub fn filter_rows(&self, where_exprs: &[Expr], engine: &Engine) -> DBResult<Vec<Cow<'_, [DBField]>>> {
+        let mut out_vec = vec![];
+        for row in &self.entries {
+            if let Some(row) = self.filter_row(row, where_exprs, engine)? {
+                out_vec.push(row);
+            }
+        }
+        Ok(out_vec)
+    }
+
+    /// The row as read if it satisfies all of 'where_exprs', None otherwise
+    ///
+    /// # Errors
+    ///
+    /// DBError::InvalidExpression if the engine fails on the row
+    fn filter_row<'r>(&self, row: &'r [DBField], where_exprs: &[Expr], engine: &Engine) -> DBResult<Option<Cow<'r, [DBField]>>> {
+        if row.len() < self.header.len() {return Ok(None)}
+        let row = self.with_virtual(row, engine.evaluator())?;
+        for expr in where_exprs {
+            match engine.eval_expr(expr, &row, &self.header) {
+                Ok(true) => (),
+                Ok(false) => return Ok(None),
+                Err(e) => return Err(DBError::InvalidExpression(e)),
+            }
+        }
+        Ok(Some(row))
+    }
+
+    /// Evaluates the projections for every row satisfying all of 'where_exprs'
+    ///
+    /// Unlike 'select_where' the projections may be any expression the engine
+    /// can evaluate. They are returned in the requested order.
+    ///
+    /// # Errors
+    ///
+    /// DBError::InvalidExpression if the engine fails on any of the rows
+    pub fn select_exprs(
+        &self,
+        projections: &[Expr],
+        where_exprs: &[Expr],
+        engine: &Engine,
+    ) -> DBResult<Vec<Vec<DBField>>> {
+        self.scan_exprs(projections, where_exprs, engine).collect()
+    }
+
+    /// Same as 'select_exprs', the rows being read, checked and projected one
+    /// at a time as the scan is advanced
+    pub fn scan_exprs<'t, E: Borrow<Engine>>(
+        &'t self,
+        projections: impl Into<Cow<'t, [Expr]>>,
+        where_exprs: impl Into<Cow<'t, [Expr]>>,
+        engine: E,
+    ) -> TableScan<'t, E> {
+        TableScan {
+            table: self,
+            rows: self.entries.iter(),
+            where_exprs: where_exprs.into(),
+            projection: Projection::Exprs(projections.into()),
+            engine,
+            failed: false,
+        }
+    }
     pub fn select_where(
         &self,
         cols: Vec<String>,
         where_exprs: &[Expr],
         engine: &Engine,
     ) -> DBResult<Vec<Vec<DBField>>> {
+        self.scan_where(cols, where_exprs, engine)?.collect()
+    }
+
+    /// Same as 'select_where', the rows being read, checked and projected one
+    /// at a time as the scan is advanced
+    ///
+    /// # Errors
+    ///
+    /// DBError::ColumnNotFound if none of 'cols' is in the header. The scan
+    /// hands out DBError::InvalidExpression for a row the engine fails on.
+    pub fn scan_where<'t, E: Borrow<Engine>>(
+        &'t self,
+        cols: Vec<String>,
+        where_exprs: impl Into<Cow<'t, [Expr]>>,
+        engine: E,
+    ) -> DBResult<TableScan<'t, E>> {
         let mut col_idx = vec![];
         let mut found = false;
         for (idx, col) in self.header.iter().enumerate() {
@@ -121,34 +543,14 @@ impl Table {
         if !found {
             return Err(DBError::ColumnNotFound(cols.iter().map(|col| col.to_string()).collect()))
         }
-        let mut max_i = 0;
-        for i in &col_idx {
-            if *i > max_i {max_i = *i}
-        }
-        let mut out_vec = vec![vec![]];
-        for row in &self.entries {
-            if row.len() < max_i + 1 {continue;}
-            let mut satisfied = true;
-
-            for expr in where_exprs {
-                if !match engine.eval_expr(expr, row, &self.header) {
-                    Ok(b) => b,
-                    Err(_) => continue,
-                }{
-                    satisfied = false;
-                    break;
-                }
-            }
-
-            if satisfied {
-                let mut out_row = Vec::new();
-                for idx in &col_idx {
-                    out_row.push(row[*idx].clone());
-                }
-                out_vec.push(out_row);
-            }
-        }
-        Ok(out_vec)
+        Ok(TableScan {
+            table: self,
+            rows: self.entries.iter(),
+            where_exprs: where_exprs.into(),
+            projection: Projection::Columns(col_idx),
+            engine,
+            failed: false,
+        })
     }
 
 }
@@ -169,40 +571,44 @@ impl Table {
             .intersperse(", ".to_string())
             .collect::<String>()
         );
-        for entry in &self.entries {
+        out_str.push('\n');
+        for entry in self.entries.iter().filter(|e| !e.is_empty()) {
             out_str.push_str(
                 &entry
                 .iter()
-                .map(|f| f.to_file_string())
+                .zip(&self.header)
+                .filter(|(_, col)| !col.is_virtual())
+                .map(|(f, _)| encode_field(f))
                 .intersperse(", ".to_string())
                 .collect::<String>()
             );
-            out_str.push_str("\n");
+            out_str.push('\n');
         }
-        out_str.push_str("\n");
-        match self.file.set_len(0) {
+        out_str.push('\n');
+        let Some(file) = &mut self.file else { return Ok(()) };
+        match file.set_len(0) {
             Ok(_) => (),
             Err(e) => return Err(DBError::FileError(Box::new(e))),
         } 
-        match self.file.write(out_str.as_bytes()) {
+        match file.write(out_str.as_bytes()) {
             Ok(_) => (),
             Err(e) => return Err(DBError::FileError(Box::new(e))),
         }
-        match self.file.flush() {
+        match file.flush() {
             Ok(_) => (),
             Err(e) => return Err(DBError::FileError(Box::new(e))),
         }
         Ok(())
     }
     pub fn load_table(&mut self) -> DBResult<()> {
-        match self.file.seek(SeekFrom::Start(0)) {
-            Err(e) => return Err(DBError::FileError(Box::new(e))),
-            _ => (),
-        };
+        let Some(file) = &mut self.file else { return Ok(()) };
+        if let Err(e) = file.seek(SeekFrom::Start(0)) {
+            return Err(DBError::FileError(Box::new(e)));
+        }
         self.entries.clear();
         self.header.clear();
         let mut contents = String::new();
-        let _ = self.file.read_to_string(&mut contents);
+        let _ = file.read_to_string(&mut contents);
         let lines_itr = contents.split('\n');
         let mut parsed_header = false;
         for (ref mut idx, line) in lines_itr.enumerate() {
@@ -210,20 +616,19 @@ impl Table {
                 continue;
             }
             if !parsed_header { 
-                let line_split_itr = line.split(',');
-                for elem in line_split_itr {
+                for elem in split_header(line) {
                     let (name, dt_type) = match elem.split_once(":") {
                         Some(s) => s,
                         _ => return Err(DBError::GenericLoadingError)
                     };
-                    let dt_type = match dt_type.trim() {
-                        "TEXT" => DataTypes::TEXT,
-                        "INT" => DataTypes::INT,
-                        _ => return Err(DBError::GenericLoadingError),
+                    let ColumnDef {dt_type, nullable, constraint, generated} = match parse_column_sql(dt_type) {
+                        Ok(c) => c,
+                        Err(e) => return Err(DBError::InvalidColumnDefinition(name.trim().to_string(), e)),
                     };
-                    let to_push: DBColumn = DBColumn {dt_type: dt_type, name: name.trim().to_string()};
+                    let to_push: DBColumn = DBColumn {dt_type, name: name.trim().to_string(), constraint, nullable, generated};
                     self.header.push(to_push);
                 }
+                self.check_generated()?;
                 *idx += 1;
                 parsed_header = true;
                 continue;
@@ -232,12 +637,23 @@ impl Table {
             if line_split.clone().count() <= 1 {continue;}
 
             let mut line_vec: Vec<DBField> = vec![];
-            for (ref mut idxb, elem) in line_split.enumerate() {
-                if self.header[*idxb].dt_type == DataTypes::TEXT {
-                    let field_to_add = DBField::Text(elem.trim().to_string());
+            let mut line_split = line_split;
+            for col in &self.header {
+                // virtual columns are not in the file
+                if col.is_virtual() {
+                    line_vec.push(DBField::Null);
+                    continue;
+                }
+                let Some(elem) = line_split.next() else {break};
+                if col.is_nullable() && elem.trim() == NULL_MARKER {
+                    line_vec.push(DBField::Null);
+                } else if col.dt_type == DataTypes::TEXT {
+                    let text = elem.trim();
+                    let text = if text.starts_with("\\\\") {&text[1..]} else {text};
+                    let field_to_add = DBField::Text(text.to_string());
                     line_vec.push(field_to_add);
-                } else if self.header[*idxb].dt_type == DataTypes::INT {
-                    let field_to_add = DBField::Int(match elem.parse::<i32>() {
+                } else if col.dt_type == DataTypes::INT {
+                    let field_to_add = DBField::Int(match elem.trim().parse::<i32>() {
                         Ok(i) => i,
                         Err(e) => {return Err(DBError::FileError(Box::new(e)))},
                     });
@@ -248,6 +664,39 @@ impl Table {
         }
         Ok(())
     }
+    /// Checks each generated column only reads the row, through the columns
+    /// that are not generated and the generated ones before it, and that the
+    /// virtual ones are not UNIQUE
+    fn check_generated(&self) -> DBResult<()> {
+        for (idx, col) in self.header.iter().enumerate() {
+            let Some(g) = &col.generated else {continue};
+            let invalid = |e: String| Err(DBError::InvalidGeneratedColumn(col.name.clone(), e));
+            if col.is_virtual() && col.is_unique() {
+                return invalid("virtual columns can not be UNIQUE".to_string());
+            }
+            let mut exprs = vec![&g.expr];
+            while let Some(expr) = exprs.pop() {
+                match expr {
+                    Expr::Identifier(name) => {
+                        let readable = self.header.iter().enumerate()
+                            .any(|(i, c)| c.name == *name && (i < idx || c.generated.is_none()));
+                        if !readable {
+                            return invalid(format!("'{}' is not a column it can read", name));
+                        }
+                    }
+                    Expr::QualifiedIdentifier { .. } => {
+                        return invalid(format!("'{}' is not a column it can read", expr));
+                    }
+                    Expr::InSubquery { .. } | Expr::Exists(_) | Expr::Window { .. } => {
+                        return invalid(format!("'{}' does not only read the row", expr));
+                    }
+                    _ => {}
+                }
+                exprs.extend(expr.children());
+            }
+        }
+        Ok(())
+    }
     pub fn new(file_name: &str) -> DBResult<Self> {
         let path = Path::new(file_name);
         let f = match File::options().append(true).read(true).open(path) {
@@ -256,11 +705,154 @@ impl Table {
         };  
         
         let tb_name = file_name.split(".").nth(0).unwrap();
-        let mut ret_db = Table{name: tb_name.to_string(), file: f, header: vec![], entries: vec![vec![]]};
+        let mut ret_db = Table{name: tb_name.to_string(), file: Some(f), header: vec![], entries: vec![], has_triggers: false};
         match ret_db.load_table() {
             Ok(_) => (),
             Err(e) => return Err(DBError::FileError(Box::new(e))),
         }
         Ok(ret_db)
     }
+
+    /// Creates the file of a new table, '<dir>/<name>.txt', holding no rows yet
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file already exists or can not be written
+    pub fn create(dir: &Path, name: &str, header: Vec<DBColumn>) -> DBResult<Self> {
+        let path = dir.join(format!("{}.{}", name, TABLE_EXTENSION));
+        let f = match File::options().create_new(true).append(true).read(true).open(path) {
+            Ok(f) => f,
+            Err(e) => return Err(DBError::FileError(Box::new(e)))
+        };
+        let mut tb = Table {name: name.to_string(), file: Some(f), header, entries: vec![], has_triggers: false};
+        tb.write_to_file()?;
+        Ok(tb)
+    }
+
+    /// Swaps the header of the table for 'header' and all its rows for 'rows',
+    /// given in the order of the new header
+    ///
+    /// # Errors
+    ///
+    /// DBError if a row does not fit the header, the table being left untouched
+    pub fn replace_rows(&mut self, header: Vec<DBColumn>, rows: Vec<Vec<DBField>>) -> DBResult<()> {
+        let old_header = std::mem::replace(&mut self.header, header);
+        let col_idx: Vec<usize> = (0..self.header.len()).collect();
+        let eval = Evaluator::new(FunctionRegistry::default());
+        let built: DBResult<Vec<Vec<DBField>>> = rows.into_iter().map(|row| self.build_row(&col_idx, row, &eval)).collect();
+        match built.and_then(|built| self.check_unique(built.iter()).map(|_| built)) {
+            Ok(built) => self.entries = built,
+            Err(e) => {
+                self.header = old_header;
+                return Err(e);
+            }
+        }
+        self.write_to_file()
+    }
+
+    /// A temporary table backed by no file, the rows are taken as they are
+    pub fn in_memory(name: &str, header: Vec<DBColumn>, entries: Vec<Vec<DBField>>) -> Self {
+        Table {name: name.to_string(), file: None, header, entries, has_triggers: false}
+    }
+}
+
+/// What a scan makes of the rows satisfying its conditions
+enum Projection<'t> {
+    /// the values at these positions
+    Columns(Vec<usize>),
+    Exprs(Cow<'t, [Expr]>),
+}
+
+/// The rows of a table read one at a time: each is only checked against the
+/// conditions and projected once the scan gets to it, so a caller may stop at
+/// any point. The scan ends after handing out an error.
+pub struct TableScan<'t, E> {
+    table: &'t Table,
+    rows: std::slice::Iter<'t, Vec<DBField>>,
+    /// borrowed from the caller, or owned by a scan outliving the query it came from
+    where_exprs: Cow<'t, [Expr]>,
+    projection: Projection<'t>,
+    engine: E,
+    failed: bool,
+}
+
+impl<E: Borrow<Engine>> TableScan<'_, E> {
+    /// The projection of the row, None if it does not satisfy the conditions
+    ///
+    /// # Errors
+    ///
+    /// DBError::InvalidExpression if the engine fails on the row
+    fn scan_row(&self, row: &[DBField]) -> DBResult<Option<Vec<DBField>>> {
+        let engine = self.engine.borrow();
+        let header = &self.table.header;
+        let Some(row) = self.table.filter_row(row, &self.where_exprs, engine)? else {
+            return Ok(None);
+        };
+        match &self.projection {
+            Projection::Columns(col_idx) => Ok(Some(col_idx.iter().map(|idx| row[*idx].clone()).collect())),
+            Projection::Exprs(projections) => {
+                let mut out_row = Vec::with_capacity(projections.len());
+                for expr in projections.iter() {
+                    match engine.evaluator().eval_value(expr, &row, header) {
+                        Ok(f) => out_row.push(f),
+                        Err(e) => return Err(DBError::InvalidExpression(e)),
+                    }
+                }
+                Ok(Some(out_row))
+            }
+        }
+    }
+}
+
+impl<E: Borrow<Engine>> Iterator for TableScan<'_, E> {
+    type Item = DBResult<Vec<DBField>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {return None}
+        while let Some(row) = self.rows.next() {
+            match self.scan_row(row) {
+                Ok(Some(out_row)) => return Some(Ok(out_row)),
+                Ok(None) => continue,
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// How NULL is written to the file
+const NULL_MARKER: &str = "\\N";
+
+/// The field as written to the file, a TEXT starting with a backslash getting
+/// another one in front so it never reads back as NULL
+fn encode_field(field: &DBField) -> String {
+    match field {
+        DBField::Null => NULL_MARKER.to_string(),
+        DBField::Text(s) if s.starts_with('\\') => format!("\\{}", s),
+        f => f.to_file_string(),
+    }
+}
+
+/// Splits a header line into its columns, on the commas outside of the
+/// parentheses and quotes of generation expressions
+fn split_header(line: &str) -> Vec<&str> {
+    let mut out = vec![];
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (idx, c) in line.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                out.push(&line[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&line[start..]);
+    out
 }
@@ -0,0 +1,67 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::ast::{ASTNode, CreateTriggerStatement, Statement};
+use crate::parser::parse_sql;
+use crate::database::errors::DBError;
+use crate::database::table::DBResult;
+
+/// Extension of the files holding trigger definitions, next to the table files
+pub const TRIGGER_EXTENSION: &str = "trigger";
+
+/* A statment run for every row inserted into 'table', stored as its
+ * 'CREATE TRIGGER' sql in '<name>.trigger'.
+ */
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub definition: CreateTriggerStatement,
+}
+
+impl Trigger {
+    /// Reads the definition file of a trigger
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file can not be read or holds no 'CREATE TRIGGER'
+    pub fn load(path: &Path) -> DBResult<Self> {
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(s) => s.to_string(),
+            None => return Err(DBError::GenericLoadingError),
+        };
+        let sql_s = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => return Err(DBError::FileError(Box::new(e))),
+        };
+        match parse_sql(sql_s.trim()) {
+            Ok(root) => match root.first_node {
+                ASTNode::Statment(Statement::CreateTrigger(definition)) => Ok(Trigger {definition}),
+                ASTNode::Statment(s) => Err(DBError::InvalidTriggerDefinition(name, format!("expected CREATE TRIGGER, found '{}'", s))),
+            },
+            Err(e) => Err(DBError::InvalidTriggerDefinition(name, e)),
+        }
+    }
+
+    /// Writes the definition file of the trigger into 'dir'
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file can not be written
+    pub fn save(&self, dir: &Path) -> DBResult<()> {
+        let mut f = match File::create(self.path(dir)) {
+            Ok(f) => f,
+            Err(e) => return Err(DBError::FileError(Box::new(e))),
+        };
+        match writeln!(f, "{}", self.definition) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(DBError::FileError(Box::new(e))),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.{}", self.definition.name, TRIGGER_EXTENSION))
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use crate::ast::{ASTNode, Statement};
+use crate::parser::parse_sql;
+use crate::database::errors::DBError;
+use crate::database::table::DBResult;
+
+/// Extension of the files holding view definitions, next to the table files
+pub const VIEW_EXTENSION: &str = "view";
+/// Same for the materialized views, whose rows are in the table of the same name
+pub const MATERIALIZED_VIEW_EXTENSION: &str = "mview";
+
+/* A named query, stored as its sql in '<name>.view' and run whenever it is
+ * selected from. A materialized one is stored in '<name>.mview', its result
+ * living in a regular table only updated on refresh.
+ */
+#[derive(Debug, Clone)]
+pub struct View {
+    pub name: String,
+    pub query: Statement,
+    pub materialized: bool,
+}
+
+impl View {
+    /// Reads the definition file of a view, named after it
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file can not be read or holds no valid query
+    pub fn load(path: &Path) -> DBResult<Self> {
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(s) => s.to_string(),
+            None => return Err(DBError::GenericLoadingError),
+        };
+        let materialized = path.extension().is_some_and(|e| e == MATERIALIZED_VIEW_EXTENSION);
+        let sql_s = match fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => return Err(DBError::FileError(Box::new(e))),
+        };
+        match parse_sql(sql_s.trim()) {
+            Ok(root) => {
+                let ASTNode::Statment(query) = root.first_node;
+                Ok(View {name, query, materialized})
+            }
+            Err(e) => Err(DBError::InvalidViewDefinition(name, e)),
+        }
+    }
+
+    /// Writes the definition file of the view into 'dir'
+    ///
+    /// # Errors
+    ///
+    /// DBError if the file can not be written
+    pub fn save(&self, dir: &Path) -> DBResult<()> {
+        let mut f = match File::create(self.path(dir)) {
+            Ok(f) => f,
+            Err(e) => return Err(DBError::FileError(Box::new(e))),
+        };
+        match writeln!(f, "{}", self.query) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(DBError::FileError(Box::new(e))),
+        }
+    }
+
+    pub fn path(&self, dir: &Path) -> std::path::PathBuf {
+        let extension = if self.materialized {MATERIALIZED_VIEW_EXTENSION} else {VIEW_EXTENSION};
+        dir.join(format!("{}.{}", self.name, extension))
+    }
+}
@@ -1,5 +1,4 @@
-#[allow(unstable_name_collisions)]
-
+#![allow(unstable_name_collisions)]
 pub mod database;
 pub mod sql;
 pub mod tests;
@@ -12,5 +11,6 @@ pub use sql::lexer::*;
 pub use sql::parser::*;
 pub use sql::ast::*;
 pub use sql::engine::*;
+pub use sql::prepared::*;
 pub use sql::*;
 pub use sql::errors as sql_errors;
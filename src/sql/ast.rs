@@ -1,14 +1,71 @@
+use std::fmt;
 use crate::sql::*;
+use crate::sql::lexer::reserved_word;
+use crate::database::{ColumnConstraint, DBField, DataTypes, GeneratedColumn};
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Insert(InsertStatement),
     Select(SelectStatement),
+    Compound(CompoundSelect),
+    With(WithStatement),
+    CreateView(CreateViewStatement),
+    DropView(String),
+    RefreshView(String),
+    CreateTrigger(CreateTriggerStatement),
+    DropTrigger(String),
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::Insert(i) => write!(f, "{}", i),
+            Statement::Select(s) => write!(f, "{}", s),
+            Statement::Compound(c) => write!(f, "{}", c),
+            Statement::With(w) => write!(f, "{}", w),
+            Statement::CreateView(v) if v.materialized => write!(f, "CREATE MATERIALIZED VIEW {} AS {}", Ident(&v.name), v.query),
+            Statement::CreateView(v) => write!(f, "CREATE VIEW {} AS {}", Ident(&v.name), v.query),
+            Statement::DropView(name) => write!(f, "DROP VIEW {}", Ident(name)),
+            Statement::RefreshView(name) => write!(f, "REFRESH MATERIALIZED VIEW {}", Ident(name)),
+            Statement::CreateTrigger(t) => write!(f, "{}", t),
+            Statement::DropTrigger(name) => write!(f, "DROP TRIGGER {}", Ident(name)),
+        }
+    }
+}
+
+impl Statement {
+    /// Calls 'f' on every expression of the statment, an expression before its
+    /// children, descending into subqueries as well as the queries of views and
+    /// the bodies of triggers
+    pub fn try_for_each_expr_mut<E>(&mut self, f: &mut impl FnMut(&mut Expr) -> Result<(), E>) -> Result<(), E> {
+        match self {
+            Statement::Insert(i) => {
+                match &mut i.source {
+                    InsertSource::Values(tuples) => tuples.iter_mut().flatten().try_for_each(|e| e.try_for_each_mut(f))?,
+                    InsertSource::Select(s) => s.try_for_each_expr_mut(f)?,
+                }
+                if let Some(OnConflict { action: ConflictAction::Update(assignments), .. }) = &mut i.on_conflict {
+                    assignments.iter_mut().try_for_each(|(_, e)| e.try_for_each_mut(f))?;
+                }
+                Ok(())
+            }
+            Statement::Select(s) => s.try_for_each_expr_mut(f),
+            Statement::Compound(c) => c.try_for_each_expr_mut(f),
+            Statement::With(w) => {
+                w.ctes.iter_mut().try_for_each(|cte| cte.query.try_for_each_expr_mut(f))?;
+                w.body.try_for_each_expr_mut(f)
+            }
+            Statement::CreateView(v) => v.query.try_for_each_expr_mut(f),
+            Statement::CreateTrigger(t) => t.body.try_for_each_expr_mut(f),
+            Statement::DropView(_) | Statement::RefreshView(_) | Statement::DropTrigger(_) => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     String(String),
     Number(i32),
+    Null,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,23 +75,578 @@ pub enum Expr {
         op: Operator,
         right: Box<Expr>,
     },
+    Unary {
+        op: Operator,
+        expr: Box<Expr>,
+    },
     Identifier(String),
+    /// 'qualifier.name', a column of the table or alias 'qualifier', of the
+    /// proposed row of an upsert as 'excluded' or the inserted row of a trigger as 'NEW'
+    QualifiedIdentifier {
+        qualifier: String,
+        name: String,
+    },
     Literal(Literal),
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+    },
+    InSubquery {
+        expr: Box<Expr>,
+        subquery: Box<SelectStatement>,
+    },
+    Exists(Box<SelectStatement>),
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        /// written 'name(*)', as in COUNT(*), which only aggregates accept
+        star: bool,
+    },
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataTypes,
+    },
+    /// 'CASE [operand] WHEN cond THEN result ... [ELSE else_result] END', with an
+    /// operand each 'cond' is a value compared to it
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+    /// 'name(args) OVER (PARTITION BY ... ORDER BY ...)', computed over the
+    /// rows of the scan rather than the current one alone
+    Window {
+        name: String,
+        args: Vec<Expr>,
+        star: bool,
+        partition_by: Vec<Expr>,
+        order_by: Vec<OrderByItem>,
+    },
+    /// A value given when the statment is run, see 'PreparedStatement'
+    Parameter(Parameter),
+}
+
+/// A placeholder of a prepared statment, '?' and '$n' being numbered from 1
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Parameter {
+    Index(usize),
+    Name(String),
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Index(n) => write!(f, "${}", n),
+            Parameter::Name(name) => write!(f, ":{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByItem {
+    pub expr: Expr,
+    pub descending: bool,
+}
+
+impl fmt::Display for OrderByItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        Ok(())
+    }
+}
+
+impl Expr {
+    /// Calls 'f' on the expression then on its children, subqueries included
+    pub fn try_for_each_mut<E>(&mut self, f: &mut impl FnMut(&mut Expr) -> Result<(), E>) -> Result<(), E> {
+        f(self)?;
+        if let Expr::InSubquery { subquery, .. } | Expr::Exists(subquery) = self {
+            subquery.try_for_each_expr_mut(f)?;
+        }
+        self.children_mut().into_iter().try_for_each(|e| e.try_for_each_mut(f))
+    }
+
+    /// The expressions directly nested in this one, subqueries not included
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Binary { left, right, .. } => vec![left, right],
+            Expr::Unary { expr, .. } => vec![expr],
+            Expr::InList { expr, list } => std::iter::once(&**expr).chain(list).collect(),
+            Expr::Between { expr, low, high } => vec![expr, low, high],
+            Expr::InSubquery { expr, .. } => vec![expr],
+            Expr::Function { args, .. } => args.iter().collect(),
+            Expr::Cast { expr, .. } => vec![expr],
+            Expr::Case { operand, branches, else_result } => operand.iter().map(|e| &**e)
+                .chain(branches.iter().flat_map(|(cond, res)| [cond, res]))
+                .chain(else_result.iter().map(|e| &**e))
+                .collect(),
+            Expr::Window { args, partition_by, order_by, .. } => args.iter()
+                .chain(partition_by)
+                .chain(order_by.iter().map(|o| &o.expr))
+                .collect(),
+            Expr::Exists(_) | Expr::Identifier(_) | Expr::QualifiedIdentifier { .. } | Expr::Literal(_)
+                | Expr::Parameter(_) => vec![],
+        }
+    }
+
+    /// Same as 'children', mutably
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Binary { left, right, .. } => vec![left, right],
+            Expr::Unary { expr, .. } => vec![expr],
+            Expr::InList { expr, list } => std::iter::once(&mut **expr).chain(list).collect(),
+            Expr::Between { expr, low, high } => vec![expr, low, high],
+            Expr::InSubquery { expr, .. } => vec![expr],
+            Expr::Function { args, .. } => args.iter_mut().collect(),
+            Expr::Cast { expr, .. } => vec![expr],
+            Expr::Case { operand, branches, else_result } => operand.iter_mut().map(|e| &mut **e)
+                .chain(branches.iter_mut().flat_map(|(cond, res)| [cond, res]))
+                .chain(else_result.iter_mut().map(|e| &mut **e))
+                .collect(),
+            Expr::Window { args, partition_by, order_by, .. } => args.iter_mut()
+                .chain(partition_by)
+                .chain(order_by.iter_mut().map(|o| &mut o.expr))
+                .collect(),
+            Expr::Exists(_) | Expr::Identifier(_) | Expr::QualifiedIdentifier { .. } | Expr::Literal(_)
+                | Expr::Parameter(_) => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Null => write!(f, "NULL"),
+        }
+    }
+}
+
+impl From<&DBField> for Literal {
+    fn from(field: &DBField) -> Self {
+        match field {
+            DBField::Int(i) => Literal::Number(*i),
+            DBField::Text(t) => Literal::String(t.clone()),
+            DBField::Null => Literal::Null,
+        }
+    }
+}
+
+/// Writes 'expr', wrapped in parentheses if it is an operation itself
+fn fmt_operand(f: &mut fmt::Formatter, expr: &Expr) -> fmt::Result {
+    match expr {
+        Expr::Binary { .. } | Expr::Unary { .. } | Expr::InList { .. } | Expr::Between { .. } | Expr::InSubquery { .. } => {
+            write!(f, "({})", expr)
+        }
+        _ => write!(f, "{}", expr),
+    }
+}
+
+fn fmt_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {write!(f, ", ")?}
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn fmt_idents(f: &mut fmt::Formatter, names: &[String]) -> fmt::Result {
+    fmt_list(f, &names.iter().map(|n| Ident(n)).collect::<Vec<_>>())
+}
+
+/// A name written back as sql, double-quoted unless it is a plain word that
+/// is not reserved
+pub struct Ident<'a>(pub &'a str);
+
+impl fmt::Display for Ident<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.0;
+        let plain = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && reserved_word(name).is_none();
+        if plain {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "\"{}\"", name.replace('"', "\"\""))
+        }
+    }
+}
+
+/// Renders the expression back as sql
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Binary { left, op, right } => {
+                fmt_operand(f, left)?;
+                match op {
+                    Operator::Like(Some(e)) | Operator::ILike(Some(e)) => {
+                        write!(f, " {} ", op)?;
+                        fmt_operand(f, right)?;
+                        write!(f, " ESCAPE {}", Literal::String(e.to_string()))
+                    }
+                    _ => {
+                        write!(f, " {} ", op)?;
+                        fmt_operand(f, right)
+                    }
+                }
+            }
+            Expr::Unary { op: Operator::Minus, expr } => {
+                write!(f, "-")?;
+                fmt_operand(f, expr)
+            }
+            Expr::Unary { op, expr } => {
+                write!(f, "{} ", op)?;
+                fmt_operand(f, expr)
+            }
+            Expr::Identifier(name) => write!(f, "{}", Ident(name)),
+            Expr::QualifiedIdentifier { qualifier, name } => write!(f, "{}.{}", Ident(qualifier), Ident(name)),
+            Expr::Literal(l) => write!(f, "{}", l),
+            Expr::Parameter(p) => write!(f, "{}", p),
+            Expr::InList { expr, list } => {
+                fmt_operand(f, expr)?;
+                write!(f, " IN (")?;
+                fmt_list(f, list)?;
+                write!(f, ")")
+            }
+            Expr::Between { expr, low, high } => {
+                fmt_operand(f, expr)?;
+                write!(f, " BETWEEN ")?;
+                fmt_operand(f, low)?;
+                write!(f, " AND ")?;
+                fmt_operand(f, high)
+            }
+            Expr::InSubquery { expr, subquery } => {
+                fmt_operand(f, expr)?;
+                write!(f, " IN ({})", subquery)
+            }
+            Expr::Exists(subquery) => write!(f, "EXISTS ({})", subquery),
+            Expr::Function { name, args, star } => {
+                write!(f, "{}(", name)?;
+                if *star {write!(f, "*")?}
+                fmt_list(f, args)?;
+                write!(f, ")")
+            }
+            Expr::Cast { expr, data_type } => write!(f, "CAST({} AS {})", expr, data_type.to_file_string()),
+            Expr::Case { operand, branches, else_result } => {
+                write!(f, "CASE")?;
+                if let Some(o) = operand {
+                    write!(f, " {}", o)?;
+                }
+                for (cond, result) in branches {
+                    write!(f, " WHEN {} THEN {}", cond, result)?;
+                }
+                if let Some(e) = else_result {
+                    write!(f, " ELSE {}", e)?;
+                }
+                write!(f, " END")
+            }
+            Expr::Window { name, args, star, partition_by, order_by } => {
+                write!(f, "{}(", name)?;
+                if *star {write!(f, "*")?}
+                fmt_list(f, args)?;
+                write!(f, ") OVER (")?;
+                if !partition_by.is_empty() {
+                    write!(f, "PARTITION BY ")?;
+                    fmt_list(f, partition_by)?;
+                    if !order_by.is_empty() {write!(f, " ")?}
+                }
+                if !order_by.is_empty() {
+                    write!(f, "ORDER BY ")?;
+                    fmt_list(f, order_by)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Select(Box<SelectStatement>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictAction {
+    Nothing,
+    Update(Vec<(String, Expr)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnConflict {
+    pub target: Option<String>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Returning {
+    All,
+    Columns(Vec<String>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct InsertStatement {
     pub table: String,
     pub columns: Option<Vec<String>>,
-    pub values: Vec<Expr>,
+    pub source: InsertSource,
+    pub on_conflict: Option<OnConflict>,
+    pub returning: Option<Returning>,
+}
+
+impl fmt::Display for InsertStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "INSERT INTO {}", Ident(&self.table))?;
+        if let Some(columns) = &self.columns {
+            write!(f, " (")?;
+            fmt_idents(f, columns)?;
+            write!(f, ")")?;
+        }
+        match &self.source {
+            InsertSource::Values(tuples) => {
+                write!(f, " VALUES ")?;
+                for (idx, tuple) in tuples.iter().enumerate() {
+                    if idx > 0 {write!(f, ", ")?}
+                    write!(f, "(")?;
+                    fmt_list(f, tuple)?;
+                    write!(f, ")")?;
+                }
+            }
+            InsertSource::Select(s) => write!(f, " {}", s)?,
+        }
+        if let Some(oc) = &self.on_conflict {
+            write!(f, " ON CONFLICT")?;
+            if let Some(target) = &oc.target {
+                write!(f, " ({})", Ident(target))?;
+            }
+            match &oc.action {
+                ConflictAction::Nothing => write!(f, " DO NOTHING")?,
+                ConflictAction::Update(assignments) => {
+                    write!(f, " DO UPDATE SET ")?;
+                    for (idx, (col, expr)) in assignments.iter().enumerate() {
+                        if idx > 0 {write!(f, ", ")?}
+                        write!(f, "{} = {}", Ident(col), expr)?;
+                    }
+                }
+            }
+        }
+        match &self.returning {
+            None => Ok(()),
+            Some(Returning::All) => write!(f, " RETURNING *"),
+            Some(Returning::Columns(columns)) => {
+                write!(f, " RETURNING ")?;
+                fmt_idents(f, columns)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectItem {
+    pub expr: Expr,
+    pub alias: Option<String>,
+}
+
+impl SelectItem {
+    /// The label of the column in the result: the alias, the name of a plain
+    /// column, or else the expression itself
+    pub fn output_name(&self) -> String {
+        match (&self.alias, &self.expr) {
+            (Some(alias), _) => alias.clone(),
+            (None, Expr::Identifier(name) | Expr::QualifiedIdentifier { name, .. }) => name.clone(),
+            (None, expr) => expr.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " AS {}", Ident(alias))?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SelectStatement {
-    pub columns: Vec<String>,
+    pub distinct: bool,
+    pub columns: Vec<SelectItem>,
     pub table: String,
+    pub table_alias: Option<String>,
     pub where_clause: Option<Vec<Expr>>,
 }
+
+impl SelectStatement {
+    /// Same as 'Statement::try_for_each_expr_mut'
+    pub fn try_for_each_expr_mut<E>(&mut self, f: &mut impl FnMut(&mut Expr) -> Result<(), E>) -> Result<(), E> {
+        self.columns.iter_mut().try_for_each(|c| c.expr.try_for_each_mut(f))?;
+        self.where_clause.iter_mut().flatten().try_for_each(|e| e.try_for_each_mut(f))
+    }
+}
+
+impl fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SELECT ")?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+        fmt_list(f, &self.columns)?;
+        write!(f, " FROM {}", Ident(&self.table))?;
+        if let Some(alias) = &self.table_alias {
+            write!(f, " AS {}", Ident(alias))?;
+        }
+        if let Some(where_exprs) = &self.where_clause {
+            write!(f, " WHERE ")?;
+            match where_exprs.as_slice() {
+                [expr] => write!(f, "{}", expr)?,
+                _ => for (idx, expr) in where_exprs.iter().enumerate() {
+                    if idx > 0 {write!(f, " AND ")?}
+                    fmt_operand(f, expr)?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperator {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+impl fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            SetOperator::Union => "UNION",
+            SetOperator::UnionAll => "UNION ALL",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        })
+    }
+}
+
+/// Selects chained by set operators, applied from left to right:
+/// 'first op_1 select_1 op_2 select_2 ...'
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundSelect {
+    pub first: SelectStatement,
+    pub rest: Vec<(SetOperator, SelectStatement)>,
+}
+
+impl fmt::Display for CompoundSelect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (op, select) in &self.rest {
+            write!(f, " {} {}", op, select)?;
+        }
+        Ok(())
+    }
+}
+
+impl CompoundSelect {
+    /// Same as 'Statement::try_for_each_expr_mut'
+    pub fn try_for_each_expr_mut<E>(&mut self, f: &mut impl FnMut(&mut Expr) -> Result<(), E>) -> Result<(), E> {
+        self.first.try_for_each_expr_mut(f)?;
+        self.rest.iter_mut().try_for_each(|(_, s)| s.try_for_each_expr_mut(f))
+    }
+
+    /// Every select of the compound, in order
+    pub fn parts(&self) -> impl Iterator<Item = &SelectStatement> {
+        std::iter::once(&self.first).chain(self.rest.iter().map(|(_, s)| s))
+    }
+}
+
+/// 'name [(columns)] AS (query)', a named query the statment can select from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cte {
+    pub name: String,
+    pub columns: Option<Vec<String>>,
+    pub query: CompoundSelect,
+}
+
+impl fmt::Display for Cte {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Ident(&self.name))?;
+        if let Some(columns) = &self.columns {
+            write!(f, " (")?;
+            fmt_idents(f, columns)?;
+            write!(f, ")")?;
+        }
+        write!(f, " AS ({})", self.query)
+    }
+}
+
+/// 'WITH [RECURSIVE] cte, ... body', later CTEs and the body seeing the earlier ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithStatement {
+    pub recursive: bool,
+    pub ctes: Vec<Cte>,
+    pub body: CompoundSelect,
+}
+
+impl fmt::Display for WithStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WITH ")?;
+        if self.recursive {
+            write!(f, "RECURSIVE ")?;
+        }
+        fmt_list(f, &self.ctes)?;
+        write!(f, " {}", self.body)
+    }
+}
+
+/// 'CREATE [MATERIALIZED] VIEW name AS query', the query being a select, compound
+/// or not, or a WITH
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateViewStatement {
+    pub name: String,
+    pub materialized: bool,
+    pub query: Box<Statement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerTiming {
+    Before,
+    After,
+}
+
+impl fmt::Display for TriggerTiming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            TriggerTiming::Before => "BEFORE",
+            TriggerTiming::After => "AFTER",
+        })
+    }
+}
+
+/// 'CREATE TRIGGER name BEFORE|AFTER INSERT ON table FOR EACH ROW body', the
+/// body being run once per inserted row with 'NEW.<col>' standing for its values
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTriggerStatement {
+    pub name: String,
+    pub timing: TriggerTiming,
+    pub table: String,
+    pub body: Box<Statement>,
+}
+
+impl fmt::Display for CreateTriggerStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CREATE TRIGGER {} {} INSERT ON {} FOR EACH ROW {}", Ident(&self.name), self.timing, Ident(&self.table), self.body)
+    }
+}
+
 #[derive(Debug)]
 pub struct ASTRootWrapper {
     pub first_node: ASTNode,
@@ -45,4 +657,12 @@ pub enum ASTNode {
     Statment(Statement),
 }
 
-
+/// A column of a table header after its name,
+/// 'TYPE [NULL] [UNIQUE|PRIMARY KEY] [GENERATED ALWAYS AS (expr) [STORED|VIRTUAL]]'
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub dt_type: DataTypes,
+    pub nullable: bool,
+    pub constraint: Option<ColumnConstraint>,
+    pub generated: Option<GeneratedColumn>,
+}
@@ -1,18 +1,105 @@
-use crate::{database, sql::{Operator, ast::{ASTNode, ASTRootWrapper, Expr, Literal, Statement}}};
-use crate::database::{DBColumn, DBField, table::Table};
-use crate::sql::errors::EngineError;
+use crate::{database, sql::{Operator, functions::{self, FunctionRegistry}, pattern, window, ast::{ASTNode, ASTRootWrapper, CompoundSelect, Cte, Expr, ConflictAction, InsertSource, InsertStatement, Literal, OnConflict, Returning, SelectStatement, SetOperator, Statement, TriggerTiming, WithStatement}}};
+use std::{cell::{Cell, RefCell}, collections::HashSet};
+use crate::database::{DBColumn, DBField, DataTypes, table::{DBResult, Table}, trigger::Trigger};
+use crate::sql::errors::{EngineError, ScriptError};
+use crate::database::errors::DBError;
 
-#[derive(Debug)]
-pub enum QueryResult {
-    Rows(Vec<Vec<DBField>>),
-    Empty,
+/// The outcome of a statment: the rows it produced, along with the names and
+/// types of their columns, and how many rows it wrote and read. Statments
+/// producing no rows come with no columns either.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryResult {
+    columns: Vec<String>,
+    types: Vec<DataTypes>,
+    rows: Vec<Vec<DBField>>,
+    rows_affected: usize,
+    rows_scanned: usize,
+}
+
+impl QueryResult {
+    /// The output column names, empty if the statment produces no rows
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The types of the output columns, in the order of 'columns'
+    pub fn types(&self) -> &[DataTypes] {
+        &self.types
+    }
+
+    pub fn rows(&self) -> &[Vec<DBField>] {
+        &self.rows
+    }
+
+    pub fn into_rows(self) -> Vec<Vec<DBField>> {
+        self.rows
+    }
+
+    /// The rows inserted or updated, or those a materialized view was
+    /// refreshed with. Rows written by triggers are not counted.
+    pub fn rows_affected(&self) -> usize {
+        self.rows_affected
+    }
+
+    /// The rows read from tables, views and CTEs, subqueries included
+    pub fn rows_scanned(&self) -> usize {
+        self.rows_scanned
+    }
+
+    /// Position of the named output column, aliases included
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns().iter().position(|c| c == name)
+    }
+}
+
+/// The rows of a query handed out one at a time, see 'Engine::run_iter'
+pub struct Cursor<'db> {
+    columns: Vec<String>,
+    types: Vec<DataTypes>,
+    rows: Box<dyn Iterator<Item = DBResult<Vec<DBField>>> + 'db>,
+}
+
+impl Cursor<'_> {
+    /// The output column names
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The types of the output columns, in the order of 'columns'. Those
+    /// of a lazy select are known before any row is read, the columns the
+    /// engine can not type beforehand being TEXT.
+    pub fn types(&self) -> &[DataTypes] {
+        &self.types
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = DBResult<Vec<DBField>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+impl From<QueryResult> for Cursor<'_> {
+    fn from(result: QueryResult) -> Self {
+        Cursor {columns: result.columns, types: result.types, rows: Box::new(result.rows.into_iter().map(Ok))}
+    }
 }
 
 pub type EngineResult<T> = Result<T, EngineError>;
 
+
+/// The rows of an insert as stored, along with its result
+type StoredRows = (Vec<Vec<DBField>>, QueryResult);
+
 ///
 /// # compares two fields according to the 'op' operator 
 ///
+/// INTs compare numerically, TEXTs lexicographically (byte-wise). The pattern
+/// operators take the TEXT on the left and the pattern on the right.
+/// Any comparison involving NULL is false.
+///
 /// # Errors
 ///
 /// EngineError on issues
@@ -21,32 +108,395 @@ pub fn compare(
     right: &DBField,
     op: &Operator,
     ) -> EngineResult<bool> {
+    if *left == DBField::Null || *right == DBField::Null {
+        return Ok(false);
+    }
     match (left, right, op) {
         (DBField::Int(a), DBField::Int(b), Operator::Equal) => Ok(a == b),
         (DBField::Int(a), DBField::Int(b), Operator::NotEqual) => Ok(a != b),
         (DBField::Int(a), DBField::Int(b), Operator::Greater) => Ok(a > b),
         (DBField::Int(a), DBField::Int(b), Operator::Smaller) => Ok(a < b),
+        (DBField::Int(a), DBField::Int(b), Operator::GreaterEqual) => Ok(a >= b),
+        (DBField::Int(a), DBField::Int(b), Operator::SmallerEqual) => Ok(a <= b),
 
         (DBField::Text(a), DBField::Text(b), Operator::Equal) => Ok(a == b),
         (DBField::Text(a), DBField::Text(b), Operator::NotEqual) => Ok(a != b),
-        _ => Err(EngineError::UnexpectedState),
+        (DBField::Text(a), DBField::Text(b), Operator::Greater) => Ok(a > b),
+        (DBField::Text(a), DBField::Text(b), Operator::Smaller) => Ok(a < b),
+        (DBField::Text(a), DBField::Text(b), Operator::GreaterEqual) => Ok(a >= b),
+        (DBField::Text(a), DBField::Text(b), Operator::SmallerEqual) => Ok(a <= b),
+        (DBField::Text(a), DBField::Text(b), Operator::Like(escape)) => Ok(pattern::like_match(a, b, *escape, false)),
+        (DBField::Text(a), DBField::Text(b), Operator::ILike(escape)) => Ok(pattern::like_match(a, b, *escape, true)),
+        (DBField::Text(a), DBField::Text(b), Operator::Glob) => Ok(pattern::glob_match(a, b)),
+        _ => Err(EngineError::TypeMismatch(op.clone(), left.clone(), right.clone())),
     }
 }
 
 ///
-/// # The struct for evaluating an ast
+/// # converts a field to the given type, NULL staying NULL
 ///
-/// ast_root wraps the first statment of sql, that is to be walked and run
+/// # Errors
+///
+/// EngineError if a TEXT does not hold a valid INT
+pub fn cast(field: DBField, data_type: &DataTypes) -> EngineResult<DBField> {
+    match (field, data_type) {
+        (DBField::Null, _) => Ok(DBField::Null),
+        (DBField::Int(i), DataTypes::TEXT) => Ok(DBField::Text(i.to_string())),
+        (DBField::Text(s), DataTypes::INT) => match s.trim().parse::<i32>() {
+            Ok(i) => Ok(DBField::Int(i)),
+            Err(_) => Err(EngineError::InvalidCast(DBField::Text(s), data_type.clone())),
+        },
+        (f, _) => Ok(f),
+    }
+}
+
+///
+/// # applies the arithmetic or concatenation operator 'op' to two fields
+///
+/// '||' accepts any fields, joining their textual forms, the rest only INTs.
+/// A NULL operand makes the result NULL.
 ///
 /// # Errors
 ///
-/// Methods, in general, return EngineError on failure
+/// EngineError on mistyped operands, division by zero or overflow
+pub fn arithmetic(
+    left: &DBField,
+    right: &DBField,
+    op: &Operator,
+    ) -> EngineResult<DBField> {
+    if *left == DBField::Null || *right == DBField::Null {
+        return Ok(DBField::Null);
+    }
+    if *op == Operator::Concat {
+        return Ok(DBField::Text(left.to_file_string() + &right.to_file_string()));
+    }
+    let (a, b) = match (left, right) {
+        (DBField::Int(a), DBField::Int(b)) => (*a, *b),
+        _ => return Err(EngineError::TypeMismatch(op.clone(), left.clone(), right.clone())),
+    };
+    if b == 0 && matches!(op, Operator::Divide | Operator::Modulo) {
+        return Err(EngineError::DivisionByZero);
+    }
+    let res = match op {
+        Operator::Plus => a.checked_add(b),
+        Operator::Minus => a.checked_sub(b),
+        Operator::Multiply => a.checked_mul(b),
+        Operator::Divide => a.checked_div(b),
+        Operator::Modulo => a.checked_rem(b),
+        _ => return Err(EngineError::UnexpectedState),
+    };
+    match res {
+        Some(n) => Ok(DBField::Int(n)),
+        None => Err(EngineError::ArithmeticOverflow(op.clone(), left.clone(), right.clone())),
+    }
+}
+
+/// Drops the 'q.' of 'q.col' identifiers whose qualifier is one of 'qualifiers',
+/// subqueries being left alone as they name their own table
+fn strip_qualifier(expr: &mut Expr, qualifiers: &[&str]) {
+    if let Expr::QualifiedIdentifier { qualifier, name } = expr {
+        if qualifiers.contains(&qualifier.as_str()) {
+            *expr = Expr::Identifier(std::mem::take(name));
+        }
+        return;
+    }
+    expr.children_mut().into_iter().for_each(|e| strip_qualifier(e, qualifiers));
+}
+
+/// The select with its columns qualified by the table name or alias resolved
+/// to plain column names
+pub(crate) fn unqualify_select(s: &SelectStatement) -> SelectStatement {
+    let mut qualifiers = vec![s.table.as_str()];
+    qualifiers.extend(s.table_alias.as_deref());
+    let mut out = s.clone();
+    out.columns.iter_mut().for_each(|c| strip_qualifier(&mut c.expr, &qualifiers));
+    out.where_clause.iter_mut().flatten().for_each(|e| strip_qualifier(e, &qualifiers));
+    out
+}
+
+/// Drops the repeated rows, keeping the first of each
+fn distinct_rows(rows: Vec<Vec<DBField>>) -> Vec<Vec<DBField>> {
+    let mut seen = HashSet::new();
+    rows.into_iter().filter(|r| seen.insert(r.clone())).collect()
+}
+
+/// Applies the set operators of a compound select to the rows of its parts,
+/// INTERSECT first, the others then from left to right
+fn combine_parts(ops: impl Iterator<Item = SetOperator>, mut parts: impl Iterator<Item = Vec<Vec<DBField>>>) -> Vec<Vec<DBField>> {
+    let mut terms = vec![(None, parts.next().unwrap_or_default())];
+    for (op, right) in ops.zip(parts) {
+        match op {
+            SetOperator::Intersect => {
+                let (left_op, left) = terms.pop().unwrap_or_default();
+                terms.push((left_op, combine_rows(op, left, right)));
+            }
+            _ => terms.push((Some(op), right)),
+        }
+    }
+    let mut terms = terms.into_iter();
+    let first = terms.next().map(|(_, rows)| rows).unwrap_or_default();
+    terms.fold(first, |rows, (op, right)| combine_rows(op.unwrap_or(SetOperator::UnionAll), rows, right))
+}
+
+/// Applies a set operator to two results, INTERSECT and EXCEPT only keeping
+/// distinct rows like UNION does
+fn combine_rows(op: SetOperator, left: Vec<Vec<DBField>>, right: Vec<Vec<DBField>>) -> Vec<Vec<DBField>> {
+    match op {
+        SetOperator::UnionAll => left.into_iter().chain(right).collect(),
+        SetOperator::Union => distinct_rows(left.into_iter().chain(right).collect()),
+        SetOperator::Intersect => {
+            let right: HashSet<_> = right.into_iter().collect();
+            distinct_rows(left.into_iter().filter(|r| right.contains(r)).collect())
+        }
+        SetOperator::Except => {
+            let right: HashSet<_> = right.into_iter().collect();
+            distinct_rows(left.into_iter().filter(|r| !right.contains(r)).collect())
+        }
+    }
+}
+
+/// Adds the column types of one more part of a compound select to those of
+/// the parts before it, 'types', a column only ever NULL so far taking the
+/// type of the new part
+///
+/// # Errors
+///
+/// EngineError naming the first column of another type than before
+fn merge_compound_types(names: &[String], types: &mut [Option<DataTypes>], part: Vec<Option<DataTypes>>) -> EngineResult<()> {
+    for ((name, seen), dt) in names.iter().zip(types.iter_mut()).zip(part) {
+        match (&*seen, dt) {
+            (Some(l), Some(r)) if *l != r => {
+                return Err(EngineError::CompoundTypeMismatch(name.clone(), l.clone(), r));
+            }
+            (None, dt) => *seen = dt,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The type of the values of an expression, known before running it from the
+/// columns it reads as 'header' declares them, the operators it applies and
+/// the functions it calls. None for NULL, which fits any type.
+pub(crate) fn expr_type(expr: &Expr, header: &[DBColumn], functions: &FunctionRegistry) -> Option<DataTypes> {
+    let of = |e: &Expr| expr_type(e, header, functions);
+    match expr {
+        Expr::Identifier(name) => header.iter().find(|c| c.name == *name).map(|c| c.data_type().clone()),
+        Expr::Literal(Literal::Number(_)) => Some(DataTypes::INT),
+        Expr::Literal(Literal::String(_)) | Expr::Binary { op: Operator::Concat, .. } => Some(DataTypes::TEXT),
+        Expr::Binary { op: Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide | Operator::Modulo, .. }
+            | Expr::Unary { op: Operator::Minus, .. } => Some(DataTypes::INT),
+        Expr::Cast { data_type, .. } => Some(data_type.clone()),
+        Expr::Case { branches, else_result, .. } => branches.iter().map(|(_, result)| result)
+            .chain(else_result.as_deref())
+            .find_map(of),
+        Expr::Function { name, .. } | Expr::Window { name, .. } if functions.return_type(name).is_some() => {
+            functions.return_type(name).cloned()
+        }
+        Expr::Function { name, args, .. } | Expr::Window { name, args, .. } => match name.as_str() {
+            "LENGTH" | "ABS" | "ROUND" | "ROW_NUMBER" | "RANK" | "DENSE_RANK" => Some(DataTypes::INT),
+            "UPPER" | "LOWER" | "TRIM" | "SUBSTR" | "REPLACE" => Some(DataTypes::TEXT),
+            "COALESCE" => args.iter().find_map(of),
+            "NULLIF" | "LAG" | "LEAD" => args.first().and_then(of),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// How a select is run: folding its aggregates over the rows, computing its
+/// windows over all of them, or projecting each row on its own as it is scanned
+enum SelectPlan<'s> {
+    Aggregate(Vec<&'s Expr>),
+    Window(Vec<&'s Expr>),
+    Scan,
+}
+
+/// Rounds a recursive CTE may take before it is deemed endless
+const MAX_RECURSION: usize = 1000;
+
+/// Whether the query of the CTE reads from the CTE itself, directly or in a subquery
+fn cte_is_recursive(cte: &Cte) -> bool {
+    cte.query.parts().any(|s| {
+        let mut subqueries = vec![];
+        collect_select_subqueries(s, &mut subqueries);
+        s.table == cte.name || subqueries.iter().any(|sq| sq.table == cte.name)
+    })
+}
+
+/// The columns of a temporary table holding query results of these types
+fn typed_header(names: &[String], types: &[DataTypes]) -> Vec<DBColumn> {
+    names.iter().zip(types).map(|(name, dt)| DBColumn::new(name, dt.clone())).collect()
+}
+
+/// The columns of the table holding a materialized view, typed as the result
+/// of its query and nullable
+fn view_header(result: &QueryResult) -> Vec<DBColumn> {
+    result.columns.iter().zip(&result.types).map(|(name, dt)| DBColumn::nullable(name, dt.clone())).collect()
+}
+
+/// The columns of the temporary table holding a CTE, typed as its query, named
+/// by its column list if it has one
+///
+/// # Errors
+///
+/// EngineError if the column list does not match the query
+fn cte_header(cte: &Cte, names: Vec<String>, types: &[DataTypes]) -> EngineResult<Vec<DBColumn>> {
+    match &cte.columns {
+        Some(columns) if columns.len() != names.len() => {
+            Err(EngineError::CteColumnCount(cte.name.clone(), columns.len(), names.len()))
+        }
+        Some(columns) => Ok(typed_header(columns, types)),
+        None => Ok(typed_header(&names, types)),
+    }
+}
+
+/// The names of the tables, or views, the statment reads from, CTEs excluded
+fn statment_tables(statment: &Statement) -> Vec<String> {
+    let mut selects = vec![];
+    let mut ctes = vec![];
+    match statment {
+        Statement::Select(s) => selects.push(s),
+        Statement::Compound(c) => selects.extend(c.parts()),
+        Statement::With(w) => {
+            for cte in &w.ctes {
+                ctes.push(cte.name.as_str());
+                selects.extend(cte.query.parts());
+            }
+            selects.extend(w.body.parts());
+        }
+        Statement::Insert(i) => if let InsertSource::Select(s) = &i.source {selects.push(s)},
+        Statement::CreateView(_) | Statement::DropView(_) | Statement::RefreshView(_) => {}
+        Statement::CreateTrigger(_) | Statement::DropTrigger(_) => {}
+    }
+    let mut subqueries = vec![];
+    selects.iter().for_each(|s| collect_select_subqueries(s, &mut subqueries));
+    collect_statment_subqueries(statment, &mut subqueries);
+    let mut names: Vec<String> = vec![];
+    for s in selects.into_iter().chain(subqueries) {
+        if !ctes.contains(&s.table.as_str()) && !names.contains(&s.table) {
+            names.push(s.table.clone());
+        }
+    }
+    names
+}
+
+/// Gathers the window function calls, those inside subqueries excluded
+fn collect_windows<'a>(expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+    if let Expr::Window { .. } = expr {
+        out.push(expr);
+        return;
+    }
+    expr.children().into_iter().for_each(|e| collect_windows(e, out));
+}
+
+/// Gathers the subqueries of an expression, nested ones before the ones holding them
+fn collect_subqueries<'a>(expr: &'a Expr, out: &mut Vec<&'a SelectStatement>) {
+    expr.children().into_iter().for_each(|e| collect_subqueries(e, out));
+    if let Expr::InSubquery { subquery, .. } | Expr::Exists(subquery) = expr {
+        collect_select_subqueries(subquery, out);
+        out.push(subquery);
+    }
+}
+
+fn collect_select_subqueries<'a>(s: &'a SelectStatement, out: &mut Vec<&'a SelectStatement>) {
+    s.columns.iter().for_each(|c| collect_subqueries(&c.expr, out));
+    s.where_clause.iter().flatten().for_each(|e| collect_subqueries(e, out));
+}
+
+fn collect_statment_subqueries<'a>(statment: &'a Statement, out: &mut Vec<&'a SelectStatement>) {
+    match statment {
+        Statement::Select(s) => collect_select_subqueries(s, out),
+        Statement::Compound(c) => c.parts().for_each(|s| collect_select_subqueries(s, out)),
+        // run as the CTEs they may read from get materialized
+        Statement::With(_) => {}
+        Statement::CreateView(_) | Statement::DropView(_) | Statement::RefreshView(_) => {}
+        Statement::CreateTrigger(_) | Statement::DropTrigger(_) => {}
+        Statement::Insert(i) => {
+            match &i.source {
+                InsertSource::Values(tuples) => tuples.iter().flatten().for_each(|e| collect_subqueries(e, out)),
+                InsertSource::Select(s) => collect_select_subqueries(s, out),
+            }
+            if let Some(OnConflict { action: ConflictAction::Update(assignments), .. }) = &i.on_conflict {
+                assignments.iter().for_each(|(_, e)| collect_subqueries(e, out));
+            }
+        }
+    }
+}
+
+/// Triggers a single insert may set off, counting those set off by the bodies
+/// of other triggers, before it is deemed endless
+const MAX_TRIGGER_DEPTH: usize = 32;
+
+/// The body of a trigger with its 'NEW.<col>' identifiers replaced by the
+/// values of the inserted row
+///
+/// # Errors
+///
+/// EngineError if the body names a column the row does not have
+fn bind_new(body: &Statement, header: &[DBColumn], row: &[DBField]) -> EngineResult<Statement> {
+    let mut body = body.clone();
+    body.try_for_each_expr_mut(&mut |expr| {
+        let Expr::QualifiedIdentifier { qualifier, name } = expr else { return Ok(()) };
+        if !qualifier.eq_ignore_ascii_case("new") {
+            return Ok(());
+        }
+        match header.iter().position(|c| c.name == *name) {
+            Some(idx) => {
+                *expr = Expr::Literal(Literal::from(&row[idx]));
+                Ok(())
+            }
+            None => Err(EngineError::ColumnNotFound(expr.to_string())),
+        }
+    })?;
+    Ok(body)
+}
+
+/// # The state expressions are evaluated with
+///
+/// The functions callable by name, along with what a statment computes ahead
+/// of its rows: the rows of its subqueries and the values of its aggregates
+/// and windows. Without a statment only expressions reading the row alone can
+/// be evaluated, as those of the generated columns of a table.
 #[derive(Debug)]
-pub struct Engine {
-    pub ast_root: ASTRootWrapper,
+pub struct Evaluator {
+    subquery_cache: RefCell<Vec<(SelectStatement, Vec<Vec<DBField>>)>>,
+    aggregate_cache: RefCell<Vec<(Expr, DBField)>>,
+    /// the values of each window function for all the scanned rows, along
+    /// with the index of the row being projected
+    window_cache: RefCell<Vec<(Expr, Vec<DBField>)>>,
+    window_row: Cell<usize>,
+    functions: RefCell<FunctionRegistry>,
 }
 
-impl Engine {
+impl Evaluator {
+    pub fn new(functions: FunctionRegistry) -> Self {
+        Evaluator {
+            subquery_cache: RefCell::new(vec![]),
+            aggregate_cache: RefCell::new(vec![]),
+            window_cache: RefCell::new(vec![]),
+            window_row: Cell::new(0),
+            functions: RefCell::new(functions),
+        }
+    }
+
+    /// Hands the cached rows of a subquery to 'f'
+    ///
+    /// # Errors
+    ///
+    /// EngineError if the subquery was not materialized, or what 'f' returns
+    fn with_subquery_rows<T>(
+        &self,
+        subquery: &SelectStatement,
+        f: impl FnOnce(&[Vec<DBField>]) -> EngineResult<T>,
+    ) -> EngineResult<T> {
+        let cache = self.subquery_cache.borrow();
+        match cache.iter().find(|(cached, _)| cached == subquery) {
+            Some((_, rows)) => f(rows),
+            None => Err(EngineError::SubqueryNotMaterialized(Box::new(subquery.clone()))),
+        }
+    }
+
     /// resolves, from a provided header, the coresponding DBField
     ///
     /// # Errors
@@ -62,12 +512,43 @@ impl Engine {
         let idx = header
             .iter()
             .position(|c| c.name == name)
-            .ok_or(EngineError::UnexpectedState)?;
+            .ok_or(EngineError::ColumnNotFound(name.to_string()))?;
         if idx + 1 > row.len() {return Err(EngineError::UnexpectedState)}
         Ok(row[idx].clone())
     }
 
-    /// Evaluates a logical expression
+    /// Picks the result of the first matching branch of a CASE, None if no
+    /// branch matches and there is no ELSE
+    ///
+    /// # Errors
+    ///
+    /// EngineError if a condition fails to evaluate
+    fn eval_case<'e>(
+        &self,
+        operand: &Option<Box<Expr>>,
+        branches: &'e [(Expr, Expr)],
+        else_result: &'e Option<Box<Expr>>,
+        row: &[DBField],
+        header: &[DBColumn],
+    ) -> EngineResult<Option<&'e Expr>> {
+        let operand = match operand {
+            Some(o) => Some(self.eval_value(o, row, header)?),
+            None => None,
+        };
+        for (cond, result) in branches {
+            let matched = match &operand {
+                Some(v) => compare(v, &self.eval_value(cond, row, header)?, &Operator::Equal)?,
+                None => self.eval_expr(cond, row, header)?,
+            };
+            if matched {
+                return Ok(Some(result));
+            }
+        }
+        Ok(else_result.as_deref())
+    }
+
+    /// Evaluates a logical expression. Comparisons with NULL are false rather
+    /// than unknown, so NOT of one holds.
     ///
     /// # Errors
     ///
@@ -85,7 +566,12 @@ impl Engine {
                     Operator::Equal
                         | Operator::NotEqual
                         | Operator::Greater
-                        | Operator::Smaller => {
+                        | Operator::Smaller
+                        | Operator::GreaterEqual
+                        | Operator::SmallerEqual
+                        | Operator::Like(_)
+                        | Operator::ILike(_)
+                        | Operator::Glob => {
                             let l = self.eval_value(left, row, header)?;
                             let r = self.eval_value(right, row, header)?;
 
@@ -103,19 +589,58 @@ impl Engine {
                             self.eval_expr(right, row, header)?
                         )
                     }
+                    _ => Err(EngineError::UnexpectedExprExpectedExpression(expr.clone())),
+                }
+            }
+            Expr::Unary { op: Operator::Not, expr: inner } => Ok(!self.eval_expr(inner, row, header)?),
+            Expr::InList { expr: inner, list } => {
+                let v = self.eval_value(inner, row, header)?;
+                for item in list {
+                    if compare(&v, &self.eval_value(item, row, header)?, &Operator::Equal)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Expr::Between { expr: inner, low, high } => {
+                let v = self.eval_value(inner, row, header)?;
+                Ok(
+                    compare(&v, &self.eval_value(low, row, header)?, &Operator::GreaterEqual)? &&
+                    compare(&v, &self.eval_value(high, row, header)?, &Operator::SmallerEqual)?
+                )
+            }
+            Expr::InSubquery { expr: inner, subquery } => {
+                let v = self.eval_value(inner, row, header)?;
+                self.with_subquery_rows(subquery, |rows| {
+                    for sq_row in rows {
+                        if sq_row.len() != 1 {
+                            return Err(EngineError::SubqueryColumnCount(sq_row.len()));
+                        }
+                        if compare(&v, &sq_row[0], &Operator::Equal)? {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                })
+            }
+            Expr::Exists(subquery) => self.with_subquery_rows(subquery, |rows| Ok(!rows.is_empty())),
+            Expr::Case { operand, branches, else_result } => {
+                match self.eval_case(operand, branches, else_result, row, header)? {
+                    Some(result) => self.eval_expr(result, row, header),
+                    None => Ok(false),
                 }
             }
             _ => Err(EngineError::UnexpectedExprExpectedExpression(expr.clone())),
         }
     }
 
-    /// Evaluates a value --- a name or a literal 
+    /// Evaluates a value --- a name, a literal or an arithmetic expression on those
     ///
     /// # Errors
     ///
     /// Returns EngineError wrraped in a Result, uses the Ok variant on succes
     /// The EngineError being described in the errors sub module
-    fn eval_value(
+    pub fn eval_value(
     &self,
     expr: &Expr,
     row: &[DBField],
@@ -125,15 +650,650 @@ impl Engine {
             Expr::Literal(l) => Ok(match l {
                 Literal::String(s) => DBField::Text(s.clone()),
                 Literal::Number(n) => DBField::Int(*n),
+                Literal::Null => DBField::Null,
             }),
 
             Expr::Identifier(name) => {
                 self.resolve_identifier(name, row, header)
             },
 
+            // left qualified, as the 'excluded.<col>' columns of an upsert
+            Expr::QualifiedIdentifier { qualifier, name } => {
+                self.resolve_identifier(&format!("{}.{}", qualifier, name), row, header)
+            },
+
+            Expr::Parameter(p) => Err(EngineError::UnboundParameter(p.to_string())),
+
+            Expr::Binary { left, op, right } if matches!(
+                op,
+                Operator::Plus
+                    | Operator::Minus
+                    | Operator::Multiply
+                    | Operator::Divide
+                    | Operator::Modulo
+                    | Operator::Concat
+            ) => {
+                let l = self.eval_value(left, row, header)?;
+                let r = self.eval_value(right, row, header)?;
+                arithmetic(&l, &r, op)
+            },
+
+            Expr::Unary { op: Operator::Minus, expr: inner } => {
+                arithmetic(&DBField::Int(0), &self.eval_value(inner, row, header)?, &Operator::Minus)
+            },
+
+            Expr::Function { name, args, star } => {
+                if self.functions.borrow().is_aggregate(name) {
+                    let cache = self.aggregate_cache.borrow();
+                    return match cache.iter().find(|(call, _)| call == expr) {
+                        Some((_, value)) => Ok(value.clone()),
+                        None => Err(EngineError::AggregateMisuse(name.clone())),
+                    };
+                }
+                if *star {
+                    return Err(EngineError::StarArgument(name.clone()));
+                }
+                let mut arg_vals = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_vals.push(self.eval_value(arg, row, header)?);
+                }
+                let user_fn = self.functions.borrow().scalar(name).cloned();
+                if let Some(f) = user_fn {
+                    return f(&arg_vals).map_err(|e| EngineError::FunctionFailed(name.clone(), e));
+                }
+                match functions::builtin(name) {
+                    Some(f) => f(&arg_vals),
+                    None => Err(EngineError::UnknownFunction(name.clone())),
+                }
+            },
+
+            Expr::Cast { expr: inner, data_type } => cast(self.eval_value(inner, row, header)?, data_type),
+
+            Expr::Window { name, .. } => {
+                let cache = self.window_cache.borrow();
+                match cache.iter().find(|(w, _)| w == expr) {
+                    Some((_, values)) => values.get(self.window_row.get()).cloned().ok_or(EngineError::UnexpectedState),
+                    None => Err(EngineError::WindowMisuse(name.clone())),
+                }
+            },
+
+            Expr::Case { operand, branches, else_result } => {
+                match self.eval_case(operand, branches, else_result, row, header)? {
+                    Some(result) => self.eval_value(result, row, header),
+                    None => Ok(DBField::Null),
+                }
+            },
+
             _ => Err(EngineError::UnexpectedExprExpectedLiteral(expr.clone())),
         }
     }
+}
+
+///
+/// # The struct for evaluating an ast
+///
+/// ast_root wraps the first statment of sql, that is to be walked and run
+///
+/// The subqueries of the statment may not refer to the outer row, so they are
+/// all run once before the statment itself and their rows kept in the cache
+/// of its evaluator.
+///
+/// # Errors
+///
+/// Methods, in general, return EngineError on failure
+#[derive(Debug)]
+pub struct Engine {
+    pub ast_root: ASTRootWrapper,
+    eval: Evaluator,
+    /// how many triggers deep the statment is being run
+    trigger_depth: usize,
+    /// the rows read so far, reported in the result
+    rows_scanned: Cell<usize>,
+}
+
+impl Engine {
+    pub fn new(ast_root: ASTRootWrapper) -> Self {
+        Engine {
+            ast_root,
+            eval: Evaluator::new(FunctionRegistry::default()),
+            trigger_depth: 0,
+            rows_scanned: Cell::new(0),
+        }
+    }
+
+    /// The engine calling 'functions' along with the built-in ones, for
+    /// 'run_on_table' which has no database to take them from
+    pub fn with_functions(self, functions: FunctionRegistry) -> Self {
+        *self.eval.functions.borrow_mut() = functions;
+        self
+    }
+
+    /// What the expressions of the statment are evaluated with
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.eval
+    }
+
+    /// Same as 'Evaluator::eval_expr'
+    pub fn eval_expr(&self, expr: &Expr, row: &[DBField], header: &[DBColumn]) -> EngineResult<bool> {
+        self.eval.eval_expr(expr, row, header)
+    }
+
+    /// Gathers the calls to aggregate functions, those inside subqueries and
+    /// windows excluded
+    fn collect_aggregates<'a>(&self, expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+        if let Expr::Window { .. } = expr {return}
+        if let Expr::Function { name, .. } = expr && self.eval.functions.borrow().is_aggregate(name) {
+            out.push(expr);
+            return;
+        }
+        expr.children().into_iter().for_each(|e| self.collect_aggregates(e, out));
+    }
+
+    /// Runs a select holding aggregates, folding all the rows passing the
+    /// filter into a single one. 'plan_select' made sure no column is read
+    /// outside of the aggregates.
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, usually an EngineError.
+    fn run_aggregate_select(
+        &self,
+        tb: &Table,
+        s: &SelectStatement,
+        aggregates: Vec<&Expr>,
+    ) -> Result<Vec<Vec<DBField>>, Box<dyn std::error::Error>> {
+        let where_exprs = s.where_clause.as_deref().unwrap_or(&[]);
+        let filtered = tb.filter_rows(where_exprs, self)?;
+        let rows: Vec<&[DBField]> = filtered.iter().map(|r| r.as_ref()).collect();
+        let header = tb.header();
+        let mut results = vec![];
+        for call in aggregates {
+            let Expr::Function { name, args, .. } = call else { continue };
+            if results.iter().any(|(done, _)| done == call) {continue}
+            let mut state = match self.eval.functions.borrow().start_aggregate(name) {
+                Some(st) => st,
+                None => return Err(Box::new(EngineError::UnknownFunction(name.clone()))),
+            };
+            for row in &rows {
+                let mut arg_vals = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_vals.push(self.eval.eval_value(arg, row, header)?);
+                }
+                state.step(&arg_vals).map_err(|e| EngineError::FunctionFailed(name.clone(), e))?;
+            }
+            let value = state.finalize().map_err(|e| EngineError::FunctionFailed(name.clone(), e))?;
+            results.push((call.clone(), value));
+        }
+
+        *self.eval.aggregate_cache.borrow_mut() = results;
+        // the columns only read the aggregates, no row is needed
+        let null_row = vec![DBField::Null; header.len()];
+        let mut out_row = Vec::with_capacity(s.columns.len());
+        for item in &s.columns {
+            match self.eval.eval_value(&item.expr, &null_row, header) {
+                Ok(f) => out_row.push(f),
+                Err(e) => {
+                    self.eval.aggregate_cache.borrow_mut().clear();
+                    return Err(Box::new(e));
+                }
+            }
+        }
+        self.eval.aggregate_cache.borrow_mut().clear();
+        Ok(vec![out_row])
+    }
+
+    /// Computes a window function for every one of the scanned rows, the values
+    /// coming in the order of 'rows'
+    ///
+    /// An aggregate over a window with an ORDER BY folds the rows of the
+    /// partition up to the last peer of the current one, without it the whole
+    /// partition.
+    ///
+    /// # Errors
+    ///
+    /// EngineError if the function is no window function, is given the wrong
+    /// arguments or fails on any of the rows
+    fn eval_window(&self, expr: &Expr, rows: &[&[DBField]], header: &[DBColumn]) -> EngineResult<Vec<DBField>> {
+        let Expr::Window { name, args, star, partition_by, order_by } = expr else {
+            return Err(EngineError::UnexpectedState);
+        };
+        let is_aggregate = self.eval.functions.borrow().is_aggregate(name);
+        if *star && !is_aggregate {
+            return Err(EngineError::StarArgument(name.clone()));
+        }
+        let (min_args, max_args) = match name.as_str() {
+            "ROW_NUMBER" | "RANK" | "DENSE_RANK" => (0, 0),
+            "LAG" | "LEAD" => (1, 3),
+            _ if is_aggregate => (0, usize::MAX),
+            _ => return Err(EngineError::NotAWindowFunction(name.clone())),
+        };
+        if args.len() < min_args || args.len() > max_args {
+            return Err(EngineError::FunctionArity(name.clone(), args.len()));
+        }
+
+        let mut partition_keys = Vec::with_capacity(rows.len());
+        let mut order_keys = Vec::with_capacity(rows.len());
+        for row in rows {
+            partition_keys.push(partition_by.iter().map(|e| self.eval.eval_value(e, row, header)).collect::<EngineResult<Vec<_>>>()?);
+            order_keys.push(order_by.iter().map(|o| self.eval.eval_value(&o.expr, row, header)).collect::<EngineResult<Vec<_>>>()?);
+        }
+        let descending: Vec<bool> = order_by.iter().map(|o| o.descending).collect();
+
+        let mut out = vec![DBField::Null; rows.len()];
+        for mut part in window::partitions(&partition_keys) {
+            window::sort_partition(&mut part, &order_keys, &descending);
+            let peers = window::peer_groups(&part, &order_keys);
+            match name.as_str() {
+                "ROW_NUMBER" | "RANK" | "DENSE_RANK" => {
+                    for (group, range) in peers.iter().enumerate() {
+                        for pos in range.clone() {
+                            let value = match name.as_str() {
+                                "ROW_NUMBER" => pos + 1,
+                                "RANK" => range.start + 1,
+                                _ => group + 1,
+                            };
+                            out[part[pos]] = DBField::Int(value as i32);
+                        }
+                    }
+                }
+                "LAG" | "LEAD" => {
+                    for (pos, &row_idx) in part.iter().enumerate() {
+                        let row = rows[row_idx];
+                        let offset = match args.get(1) {
+                            Some(e) => match self.eval.eval_value(e, row, header)? {
+                                DBField::Int(i) if i >= 0 => i as usize,
+                                f => return Err(EngineError::FunctionArgumentType(name.clone(), f)),
+                            },
+                            None => 1,
+                        };
+                        let target = if name == "LAG" {
+                            pos.checked_sub(offset)
+                        } else {
+                            pos.checked_add(offset).filter(|t| *t < part.len())
+                        };
+                        out[row_idx] = match (target, args.get(2)) {
+                            (Some(t), _) => self.eval.eval_value(&args[0], rows[part[t]], header)?,
+                            (None, Some(default)) => self.eval.eval_value(default, row, header)?,
+                            (None, None) => DBField::Null,
+                        };
+                    }
+                }
+                _ => {
+                    for range in &peers {
+                        let mut state = match self.eval.functions.borrow().start_aggregate(name) {
+                            Some(st) => st,
+                            None => return Err(EngineError::UnknownFunction(name.clone())),
+                        };
+                        let frame_end = if order_by.is_empty() {part.len()} else {range.end};
+                        for &row_idx in &part[..frame_end] {
+                            let arg_vals = args.iter().map(|a| self.eval.eval_value(a, rows[row_idx], header)).collect::<EngineResult<Vec<_>>>()?;
+                            state.step(&arg_vals).map_err(|e| EngineError::FunctionFailed(name.clone(), e))?;
+                        }
+                        let value = state.finalize().map_err(|e| EngineError::FunctionFailed(name.clone(), e))?;
+                        for pos in range.clone() {
+                            out[part[pos]] = value.clone();
+                        }
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Runs a select holding window functions: each is computed over all the
+    /// rows passing the filter before the projections are evaluated row by row
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, usually an EngineError.
+    fn run_window_select(
+        &self,
+        tb: &Table,
+        s: &SelectStatement,
+        windows: Vec<&Expr>,
+    ) -> Result<Vec<Vec<DBField>>, Box<dyn std::error::Error>> {
+        let where_exprs = s.where_clause.as_deref().unwrap_or(&[]);
+        let filtered = tb.filter_rows(where_exprs, self)?;
+        let rows: Vec<&[DBField]> = filtered.iter().map(|r| r.as_ref()).collect();
+        let header = tb.header();
+        let mut results: Vec<(Expr, Vec<DBField>)> = vec![];
+        for w in windows {
+            if results.iter().any(|(done, _)| done == w) {continue}
+            results.push((w.clone(), self.eval_window(w, &rows, header)?));
+        }
+
+        *self.eval.window_cache.borrow_mut() = results;
+        let mut out_vec = vec![];
+        for (idx, row) in rows.iter().enumerate() {
+            self.eval.window_row.set(idx);
+            let mut out_row = Vec::with_capacity(s.columns.len());
+            for item in &s.columns {
+                match self.eval.eval_value(&item.expr, row, header) {
+                    Ok(f) => out_row.push(f),
+                    Err(e) => {
+                        self.eval.window_cache.borrow_mut().clear();
+                        return Err(Box::new(e));
+                    }
+                }
+            }
+            out_vec.push(out_row);
+        }
+        self.eval.window_cache.borrow_mut().clear();
+        Ok(out_vec)
+    }
+
+    /// Runs every subquery of the statment not yet in the cache, 'lookup'
+    /// resolving the tables they read from
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, an EngineError if a table is missing, otherwise
+    /// whatever running the subquery failed with
+    fn materialize_subqueries<'t>(
+        &self,
+        statment: &Statement,
+        lookup: impl Fn(&str) -> Option<&'t Table>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut subqueries = vec![];
+        collect_statment_subqueries(statment, &mut subqueries);
+        for sq in subqueries {
+            if self.eval.subquery_cache.borrow().iter().any(|(cached, _)| cached == sq) {continue}
+            let rows = match lookup(&sq.table) {
+                Some(tb) => self.run_select(tb, sq)?,
+                None => return Err(Box::new(EngineError::TableNotFound(sq.table.clone()))),
+            };
+            self.eval.subquery_cache.borrow_mut().push((sq.clone(), rows));
+        }
+        Ok(())
+    }
+
+    /// Turns a tuple of a VALUES clause into the fields to be inserted
+    ///
+    /// # Errors
+    ///
+    /// EngineError if an expression can not be evaluated without a row,
+    /// e.g. it names a column
+    fn eval_insert_tuple(&self, tuple: &[Expr]) -> EngineResult<Vec<DBField>> {
+        let mut fields: Vec<DBField> = vec![];
+        for val in tuple {
+            fields.push(self.eval.eval_value(val, &[], &[])?);
+        }
+        Ok(fields)
+    }
+
+    /// Runs a select statment against the given table
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, usually a DBError.
+    fn run_select(&self, tb: &Table, s: &SelectStatement) -> Result<Vec<Vec<DBField>>, Box<dyn std::error::Error>> {
+        let s = &unqualify_select(s);
+        self.rows_scanned.set(self.rows_scanned.get() + tb.row_count());
+        let rows = match self.plan_select(s)? {
+            SelectPlan::Aggregate(aggregates) => self.run_aggregate_select(tb, s, aggregates)?,
+            SelectPlan::Window(windows) => self.run_window_select(tb, s, windows)?,
+            SelectPlan::Scan => {
+                let where_exprs = s.where_clause.as_deref().unwrap_or(&[]);
+                let projections: Vec<Expr> = s.columns.iter().map(|c| c.expr.clone()).collect();
+                tb.select_exprs(&projections, where_exprs, self)?
+            }
+        };
+        Ok(if s.distinct {distinct_rows(rows)} else {rows})
+    }
+
+    /// How the unqualified select 's' is to be run
+    ///
+    /// # Errors
+    ///
+    /// EngineError if a function other than an aggregate is called with '*',
+    /// or a column is read outside of the aggregates of the select
+    fn plan_select<'s>(&self, s: &'s SelectStatement) -> EngineResult<SelectPlan<'s>> {
+        let where_exprs = s.where_clause.as_deref().unwrap_or(&[]);
+        for expr in s.columns.iter().map(|c| &c.expr).chain(where_exprs) {
+            self.check_star_calls(expr)?;
+        }
+        let mut aggregates = vec![];
+        s.columns.iter().for_each(|c| self.collect_aggregates(&c.expr, &mut aggregates));
+        if !aggregates.is_empty() {
+            if let Some(name) = s.columns.iter().find_map(|c| self.ungrouped_column(&c.expr)) {
+                return Err(EngineError::UngroupedColumn(name.clone()));
+            }
+            return Ok(SelectPlan::Aggregate(aggregates));
+        }
+        let mut windows = vec![];
+        s.columns.iter().for_each(|c| collect_windows(&c.expr, &mut windows));
+        match windows.is_empty() {
+            true => Ok(SelectPlan::Scan),
+            false => Ok(SelectPlan::Window(windows)),
+        }
+    }
+
+    /// Checks only aggregates are called with '*', subqueries excluded
+    ///
+    /// # Errors
+    ///
+    /// EngineError naming the first other function called so
+    fn check_star_calls(&self, expr: &Expr) -> EngineResult<()> {
+        match expr {
+            Expr::Function { name, star: true, .. } | Expr::Window { name, star: true, .. }
+                if !self.eval.functions.borrow().is_aggregate(name) => Err(EngineError::StarArgument(name.clone())),
+            _ => expr.children().into_iter().try_for_each(|e| self.check_star_calls(e)),
+        }
+    }
+
+    /// The first column read outside of aggregate calls, windows and subqueries
+    fn ungrouped_column<'a>(&self, expr: &'a Expr) -> Option<&'a String> {
+        match expr {
+            Expr::Identifier(name) | Expr::QualifiedIdentifier { name, .. } => Some(name),
+            Expr::Window { .. } => None,
+            Expr::Function { name, .. } if self.eval.functions.borrow().is_aggregate(name) => None,
+            _ => expr.children().into_iter().find_map(|e| self.ungrouped_column(e)),
+        }
+    }
+
+    /// Runs each part of a compound select against its table, 'lookup'
+    /// resolving them, and combines the results, INTERSECT binding tighter
+    /// than the other operators. The columns of the parts are typed, and
+    /// checked against each other, before any of them runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, an EngineError if a table is missing or the parts
+    /// do not agree on the count or types of their columns, otherwise whatever
+    /// a part failed with
+    fn run_compound<'t>(
+        &self,
+        c: &CompoundSelect,
+        lookup: impl Fn(&str) -> Option<&'t Table>,
+    ) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let columns: Vec<String> = c.first.columns.iter().map(|col| col.output_name()).collect();
+        let mut tables = vec![];
+        let mut types = vec![None; columns.len()];
+        for s in c.parts() {
+            if s.columns.len() != columns.len() {
+                return Err(Box::new(EngineError::CompoundColumnCount(columns.len(), s.columns.len())));
+            }
+            let Some(tb) = lookup(&s.table) else {
+                return Err(Box::new(EngineError::TableNotFound(s.table.clone())));
+            };
+            merge_compound_types(&columns, &mut types, self.select_column_types(tb, s))?;
+            tables.push(tb);
+        }
+        let mut parts = vec![];
+        for (s, tb) in c.parts().zip(tables) {
+            parts.push(self.run_select(tb, s)?);
+        }
+        let rows = combine_parts(c.rest.iter().map(|(op, _)| *op), parts.into_iter());
+        let types = types.into_iter().map(|dt| dt.unwrap_or(DataTypes::TEXT)).collect();
+        Ok(QueryResult {columns, types, rows, ..Default::default()})
+    }
+
+    /// Materializes the CTEs one after the other as temporary tables, each
+    /// seeing those before it, then runs the body against them and the tables
+    /// 'lookup' resolves. A CTE shadows a table of the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, an EngineError if a table is missing or a CTE is
+    /// malformed, otherwise whatever running a query failed with
+    fn run_with<'t>(
+        &self,
+        w: &WithStatement,
+        lookup: impl Fn(&str) -> Option<&'t Table>,
+    ) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let mut ctes: Vec<Table> = vec![];
+        for cte in &w.ctes {
+            let scope = |name: &str| ctes.iter().find(|tb| tb.name == name).or_else(|| lookup(name));
+            let table = if w.recursive && cte_is_recursive(cte) {
+                self.run_recursive_cte(cte, scope)?
+            } else {
+                self.materialize_subqueries(&Statement::Compound(cte.query.clone()), scope)?;
+                let result = self.run_compound(&cte.query, scope)?;
+                Table::in_memory(&cte.name, cte_header(cte, result.columns, &result.types)?, result.rows)
+            };
+            // the cached subqueries may have read an earlier state of the CTEs
+            self.eval.subquery_cache.borrow_mut().clear();
+            ctes.push(table);
+        }
+        let scope = |name: &str| ctes.iter().find(|tb| tb.name == name).or_else(|| lookup(name));
+        self.materialize_subqueries(&Statement::Compound(w.body.clone()), scope)?;
+        self.run_compound(&w.body, scope)
+    }
+
+    /// Runs a CTE referring to itself: the first select seeds the rows, the
+    /// others are then run over and over against the rows the previous round
+    /// added, until no new ones come up. With UNION the rows already produced
+    /// are not added again.
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, an EngineError if the parts are not combined with
+    /// UNION [ALL] or the recursion does not end, otherwise whatever running a
+    /// select failed with
+    fn run_recursive_cte<'t>(
+        &self,
+        cte: &Cte,
+        lookup: impl Fn(&str) -> Option<&'t Table>,
+    ) -> Result<Table, Box<dyn std::error::Error>> {
+        if cte.query.rest.iter().any(|(op, _)| !matches!(op, SetOperator::Union | SetOperator::UnionAll)) {
+            return Err(Box::new(EngineError::RecursiveCteOperator(cte.name.clone())));
+        }
+        let distinct = cte.query.rest.iter().any(|(op, _)| *op == SetOperator::Union);
+        let anchor = CompoundSelect {first: cte.query.first.clone(), rest: vec![]};
+        self.materialize_subqueries(&Statement::Compound(anchor.clone()), &lookup)?;
+        let result = self.run_compound(&anchor, &lookup)?;
+        let mut rows = if distinct {distinct_rows(result.rows)} else {result.rows};
+        let header = cte_header(cte, result.columns, &result.types)?;
+        let names: Vec<String> = header.iter().map(|c| c.name.clone()).collect();
+        // the steps are typed against the columns of the rows they read
+        let mut types = match lookup(&anchor.first.table) {
+            Some(tb) => self.select_column_types(tb, &anchor.first),
+            None => vec![None; names.len()],
+        };
+        let empty = Table::in_memory(&cte.name, header.clone(), vec![]);
+        for (_, step) in &cte.query.rest {
+            if step.columns.len() != names.len() {
+                return Err(Box::new(EngineError::CompoundColumnCount(names.len(), step.columns.len())));
+            }
+            let tb = if step.table == cte.name {Some(&empty)} else {lookup(&step.table)};
+            if let Some(tb) = tb {
+                merge_compound_types(&names, &mut types, self.select_column_types(tb, step))?;
+            }
+        }
+        // a column only NULL in the anchor takes the type of the steps
+        let types: Vec<DataTypes> = types.into_iter().map(|dt| dt.unwrap_or(DataTypes::TEXT)).collect();
+        let header = typed_header(&names, &types);
+        let mut seen: HashSet<Vec<DBField>> = if distinct {rows.iter().cloned().collect()} else {HashSet::new()};
+        let mut working = rows.clone();
+        let mut round = 0;
+        while !working.is_empty() {
+            round += 1;
+            if round > MAX_RECURSION {
+                return Err(Box::new(EngineError::RecursionLimit(cte.name.clone(), MAX_RECURSION)));
+            }
+            let table = Table::in_memory(&cte.name, header.clone(), working);
+            let scope = |name: &str| if name == cte.name {Some(&table)} else {lookup(name)};
+            self.eval.subquery_cache.borrow_mut().clear();
+            let mut added = vec![];
+            for (_, step) in &cte.query.rest {
+                self.materialize_subqueries(&Statement::Select(step.clone()), scope)?;
+                let step_rows = match scope(&step.table) {
+                    Some(tb) => self.run_select(tb, step)?,
+                    None => return Err(Box::new(EngineError::TableNotFound(step.table.clone()))),
+                };
+                added.extend(step_rows);
+            }
+            if distinct {
+                added.retain(|r| seen.insert(r.clone()));
+            }
+            rows.extend(added.iter().cloned());
+            working = added;
+        }
+        Ok(Table::in_memory(&cte.name, header, rows))
+    }
+
+    /// Inserts all the rows in one go, the table file being rewritten once
+    ///
+    /// With a RETURNING clause the stored rows are handed back, projected on the
+    /// requested columns, otherwise the result is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, usually a DBError.
+    fn run_insert(&self, tb: &mut Table, i: &InsertStatement, rows: Vec<Vec<DBField>>) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        Ok(self.store_rows(tb, i, rows)?.1)
+    }
+
+    /// Adds the rows to the table, returning them as stored along with the
+    /// result of the insert, the RETURNING columns typed as the table declares them
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, usually a DBError. A missing RETURNING column
+    /// is reported before any row is stored.
+    fn store_rows(&self, tb: &mut Table, i: &InsertStatement, rows: Vec<Vec<DBField>>) -> Result<StoredRows, Box<dyn std::error::Error>> {
+        let col_idx = match &i.returning {
+            None => None,
+            Some(Returning::All) => Some((0..tb.header().len()).collect::<Vec<_>>()),
+            Some(Returning::Columns(c)) => Some(tb.column_indices(&c.iter().map(|cs| cs.as_str()).collect::<Vec<_>>())?),
+        };
+        let cols = i.columns.as_ref().map(|s| s.iter().map(|cs| cs.as_str()).collect());
+        let stored = match &i.on_conflict {
+            Some(oc) => tb.store_upsert(cols, rows, oc, self)?,
+            None => tb.store_many(cols, rows, &self.eval.functions.borrow())?,
+        };
+        let rows_affected = stored.len();
+        let Some(col_idx) = col_idx else {
+            return Ok((stored, QueryResult {rows_affected, ..Default::default()}));
+        };
+        let rows = stored.iter().map(|row| col_idx.iter().map(|idx| row[*idx].clone()).collect()).collect();
+        let result = QueryResult {
+            columns: col_idx.iter().map(|idx| tb.header()[*idx].name.clone()).collect(),
+            types: col_idx.iter().map(|idx| tb.header()[*idx].data_type().clone()).collect(),
+            rows,
+            rows_affected,
+            rows_scanned: 0,
+        };
+        Ok((stored, result))
+    }
+
+    /// The result of a select against 'tb', typed by 'select_types'
+    fn select_result(&self, tb: &Table, s: &SelectStatement, rows: Vec<Vec<DBField>>) -> QueryResult {
+        let columns = s.columns.iter().map(|c| c.output_name()).collect();
+        QueryResult {columns, types: self.select_types(tb, s), rows, ..Default::default()}
+    }
+
+    /// The types of the columns of a select against 'tb', TEXT for those
+    /// only ever NULL
+    fn select_types(&self, tb: &Table, s: &SelectStatement) -> Vec<DataTypes> {
+        self.select_column_types(tb, s).into_iter().map(|dt| dt.unwrap_or(DataTypes::TEXT)).collect()
+    }
+
+    /// Same as 'select_types', None for the columns only ever NULL
+    fn select_column_types(&self, tb: &Table, s: &SelectStatement) -> Vec<Option<DataTypes>> {
+        let functions = self.eval.functions.borrow();
+        unqualify_select(s).columns.iter().map(|c| expr_type(&c.expr, tb.header(), &functions)).collect()
+    }
 
     /// Evaluates the AST
     ///
@@ -143,68 +1303,332 @@ impl Engine {
     /// described the errors sub-module.
     /// also possible are database based errors.
     pub fn run_on_table(&self, db: &mut Table, statment: &Statement) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        self.eval.subquery_cache.borrow_mut().clear();
+        self.rows_scanned.set(0);
+        self.materialize_subqueries(statment, |name| if name == db.name {Some(&*db)} else {None})?;
+        let result = self.run_on_table_materialized(db, statment)?;
+        Ok(QueryResult {rows_scanned: self.rows_scanned.get(), ..result})
+    }
+
+    fn run_on_table_materialized(&self, db: &mut Table, statment: &Statement) -> Result<QueryResult, Box<dyn std::error::Error>> {
         match statment {
+            Statement::Insert(_) if db.has_triggers() => Err(Box::new(DBError::TriggersNotRun(db.name.clone()))),
             Statement::Insert(i) => {
-                let mut field_to_insert: Vec<DBField> = vec![];
-                for val in &i.values {
-                    match val {
-                        Expr::Literal(l) => match l {
-                            Literal::String(s) => field_to_insert.push(DBField::Text(s.clone())),
-                            Literal::Number(n) => field_to_insert.push(DBField::Int(*n)),
-                        },
-
-                        _ => {
-                            return Err(Box::new(
-                                    EngineError::UnexpectedExprExpectedLiteral(val.clone())
-                            ));
+                let rows = match &i.source {
+                    InsertSource::Values(tuples) => {
+                        let mut rows = vec![];
+                        for tuple in tuples {
+                            rows.push(self.eval_insert_tuple(tuple)?);
                         }
-                    }
-                }
-
-                match &i.columns {
-                    Some(s) => db.insert(Option::Some(s.iter().map(|cs| cs.as_str()).collect()), field_to_insert)?,
-                    None => db.insert(Option::None, field_to_insert)?,
-                }
-                return Ok(QueryResult::Empty)
+                        rows
+                    },
+                    InsertSource::Select(s) => {
+                        if s.table != db.name {
+                            return Err(Box::new(EngineError::TableNotFound(s.table.clone())));
+                        }
+                        self.run_select(db, s)?
+                    },
+                };
+                self.run_insert(db, i, rows)
             },
             Statement::Select(s) => {
-                match &s.where_clause {
-                    None => {
-                        let r = db.select_cols(
-                            s.columns.iter().map(|c| c.as_str()).collect()
-                        );
-                        Ok(QueryResult::Rows(r?))
-                    }
-                    Some(where_exprs) => {
-                        let r = db.select_where(
-                            s.columns.clone(),
-                            where_exprs,
-                            self,
-                        )?;
-                        Ok(QueryResult::Rows(r))
-                    }
-                }
+                let rows = self.run_select(db, s)?;
+                Ok(self.select_result(db, s, rows))
+            }
+            Statement::Compound(c) => self.run_compound(c, |name| if name == db.name {Some(&*db)} else {None}),
+            Statement::With(w) => self.run_with(w, |name| if name == db.name {Some(&*db)} else {None}),
+            Statement::CreateView(_) | Statement::DropView(_) | Statement::RefreshView(_)
+            | Statement::CreateTrigger(_) | Statement::DropTrigger(_) => {
+                Err(Box::new(EngineError::NeedsDatabase(statment.to_string())))
             }
         }
     }
-    pub fn run(&self, mut db: database::db::DB) -> Result<QueryResult, Box<dyn std::error::Error>> {
-        let statment = match &self.ast_root.first_node {
-            ASTNode::Statment(s) => s,
-        };
-        let tb = match statment {
-            Statement::Insert(is) => {
-                match db.get_mut_table(&is.table) {
-                    Some(tb) => tb,
-                    None => return Err(Box::new(EngineError::UnexpectedState)), // add no such table error 
+
+    /// Runs every view the statment reads from into a temporary table, the
+    /// views named in 'expanding' being those already being run
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, an EngineError if the views refer to each other
+    /// in a loop, otherwise whatever running a view failed with
+    fn materialize_views(
+        &self,
+        statment: &Statement,
+        db: &database::db::DB,
+        expanding: &[String],
+    ) -> Result<Vec<Table>, Box<dyn std::error::Error>> {
+        let mut views: Vec<Table> = vec![];
+        for name in statment_tables(statment) {
+            if db.get_table(&name).is_some() {continue}
+            let Some(view) = db.get_view(&name) else {continue};
+            if expanding.contains(&name) {
+                return Err(Box::new(EngineError::ViewCycle(name)));
+            }
+            let expanding: Vec<String> = expanding.iter().cloned().chain([name.clone()]).collect();
+            let engine = Engine::new(ASTRootWrapper {first_node: ASTNode::Statment(view.query.clone())});
+            let result = engine.run_read(db, &expanding)?;
+            self.rows_scanned.set(self.rows_scanned.get() + result.rows_scanned);
+            let header = typed_header(&result.columns, &result.types);
+            views.push(Table::in_memory(&name, header, result.rows));
+        }
+        Ok(views)
+    }
+
+    /// Readies the engine for running its statment against 'db', with the
+    /// functions registered on it and nothing cached or counted yet
+    fn start_run(&self, db: &database::db::DB) {
+        *self.eval.functions.borrow_mut() = db.functions().clone();
+        self.eval.subquery_cache.borrow_mut().clear();
+        self.rows_scanned.set(0);
+    }
+
+    /// Runs a statment only reading rows, a select, compound or not, or a WITH,
+    /// against the tables and views of the database
+    ///
+    /// # Errors
+    ///
+    /// Same as 'run_on_db'
+    fn run_read(&self, db: &database::db::DB, expanding: &[String]) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ASTNode::Statment(statment) = &self.ast_root.first_node;
+        self.start_run(db);
+        let views = self.materialize_views(statment, db, expanding)?;
+        let scope = |name: &str| db.get_table(name).or_else(|| views.iter().find(|tb| tb.name == name));
+        self.materialize_subqueries(statment, scope)?;
+        let result = match statment {
+            Statement::Select(s) => match scope(&s.table) {
+                Some(tb) => {
+                    let rows = self.run_select(tb, s)?;
+                    self.select_result(tb, s, rows)
                 }
+                None => return Err(Box::new(EngineError::TableNotFound(s.table.clone()))),
             },
-            Statement::Select(ss) => {
-                match db.get_mut_table(&ss.table) {
-                    Some(tb) => tb,
-                    None => return Err(Box::new(EngineError::UnexpectedState)), // add no such table error 
+            Statement::Compound(c) => self.run_compound(c, scope)?,
+            Statement::With(w) => self.run_with(w, scope)?,
+            _ => return Err(Box::new(EngineError::UnexpectedState)),
+        };
+        Ok(QueryResult {rows_scanned: self.rows_scanned.get(), ..result})
+    }
+
+    /// Same as 'run_read_only', the rows being handed out as the cursor is
+    /// advanced. A plain select on a table, with no aggregate, window or
+    /// DISTINCT, is run lazily, each row being read, filtered and projected
+    /// only once the cursor gets to it. Other queries are run to the end
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Same as 'run_read_only'. The rows of a lazy select fail as those of
+    /// 'Table::select_exprs' do, when the cursor gets to them.
+    pub fn run_iter(self, db: &database::db::DB) -> Result<Cursor<'_>, Box<dyn std::error::Error>> {
+        let ASTNode::Statment(statment) = &self.ast_root.first_node;
+        let Statement::Select(select) = statment else {
+            return self.run_read_only(db).map(Cursor::from);
+        };
+        self.start_run(db);
+        let s = unqualify_select(select);
+        // views are run into temporary tables the cursor could not borrow
+        let only_tables = statment_tables(statment).iter().all(|name| db.get_table(name).is_some());
+        let lazy = only_tables && !s.distinct && matches!(self.plan_select(&s)?, SelectPlan::Scan);
+        let Some(tb) = db.get_table(&s.table).filter(|_| lazy) else {
+            return self.run_read_only(db).map(Cursor::from);
+        };
+        self.materialize_subqueries(statment, |name| db.get_table(name))?;
+        let columns = select.columns.iter().map(|c| c.output_name()).collect();
+        let types = self.select_types(tb, select);
+        let projections: Vec<Expr> = s.columns.into_iter().map(|c| c.expr).collect();
+        let where_exprs = s.where_clause.unwrap_or_default();
+        Ok(Cursor {columns, types, rows: Box::new(tb.scan_exprs(projections, where_exprs, self))})
+    }
+
+    /// Runs a statment only reading rows against the database, which is left
+    /// untouched
+    ///
+    /// # Errors
+    ///
+    /// EngineError if the statment would change the database, otherwise same
+    /// as 'run_on_db'
+    pub fn run_read_only(&self, db: &database::db::DB) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ASTNode::Statment(statment) = &self.ast_root.first_node;
+        match statment {
+            Statement::Select(_) | Statement::Compound(_) | Statement::With(_) => self.run_read(db, &[]),
+            _ => Err(Box::new(EngineError::NotReadOnly(statment.to_string()))),
+        }
+    }
+
+    /// Evaluates the AST against the whole database, resolving the tables and
+    /// views by name and calling the functions registered on it
+    ///
+    /// # Errors
+    ///
+    /// Same as 'run_on_table', additionally an EngineError if a table is missing
+    pub fn run_on_db(&self, db: &mut database::db::DB) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ASTNode::Statment(statment) = &self.ast_root.first_node;
+        let is = match statment {
+            Statement::Select(_) | Statement::Compound(_) | Statement::With(_) => return self.run_read(db, &[]),
+            Statement::CreateView(cv) => {
+                // running the query once checks the tables and columns it reads exist
+                let engine = Engine::new(ASTRootWrapper {first_node: ASTNode::Statment((*cv.query).clone())});
+                let result = engine.run_read(db, std::slice::from_ref(&cv.name))?;
+                if cv.materialized {
+                    db.create_materialized_view(&cv.name, (*cv.query).clone(), view_header(&result), result.rows)?;
+                } else {
+                    db.create_view(&cv.name, (*cv.query).clone())?;
+                }
+                return Ok(QueryResult::default());
+            }
+            Statement::DropView(name) => {
+                db.drop_view(name)?;
+                return Ok(QueryResult::default());
+            }
+            Statement::RefreshView(name) => {
+                let query = match db.get_view(name) {
+                    Some(view) if view.materialized => view.query.clone(),
+                    _ => return Err(Box::new(DBError::ViewNotFound(name.clone()))),
+                };
+                let engine = Engine::new(ASTRootWrapper {first_node: ASTNode::Statment(query)});
+                let result = engine.run_read(db, std::slice::from_ref(name))?;
+                let Some(tb) = db.get_mut_table(name) else {
+                    return Err(Box::new(EngineError::TableNotFound(name.clone())));
+                };
+                let (rows_affected, rows_scanned) = (result.rows.len(), result.rows_scanned);
+                // the types the query leaves to its values may differ from those of the last run
+                tb.replace_rows(view_header(&result), result.rows)?;
+                return Ok(QueryResult {rows_affected, rows_scanned, ..Default::default()});
+            }
+            Statement::CreateTrigger(ct) => {
+                if db.get_view(&ct.table).is_some() {
+                    return Err(Box::new(EngineError::ViewNotWritable(ct.table.clone())));
+                }
+                let Some(tb) = db.get_table(&ct.table) else {
+                    return Err(Box::new(EngineError::TableNotFound(ct.table.clone())));
+                };
+                // binding a row of NULLs checks the body only names columns of the table
+                bind_new(&ct.body, tb.header(), &vec![DBField::Null; tb.header().len()])?;
+                db.create_trigger(Trigger {definition: ct.clone()})?;
+                return Ok(QueryResult::default());
+            }
+            Statement::DropTrigger(name) => {
+                db.drop_trigger(name)?;
+                return Ok(QueryResult::default());
+            }
+            Statement::Insert(is) => is,
+        };
+        if db.get_view(&is.table).is_some() {
+            return Err(Box::new(EngineError::ViewNotWritable(is.table.clone())));
+        }
+        self.start_run(db);
+        let views = self.materialize_views(statment, db, &[])?;
+        let scope = |name: &str| db.get_table(name).or_else(|| views.iter().find(|tb| tb.name == name));
+        self.materialize_subqueries(statment, scope)?;
+        let rows = match &is.source {
+            InsertSource::Values(tuples) => {
+                let mut rows = vec![];
+                for tuple in tuples {
+                    rows.push(self.eval_insert_tuple(tuple)?);
                 }
+                rows
+            }
+            InsertSource::Select(ss) => match scope(&ss.table) {
+                Some(src) => self.run_select(src, ss)?,
+                None => return Err(Box::new(EngineError::TableNotFound(ss.table.clone()))),
             },
         };
-        self.run_on_table(tb, statment)
+        let before: Vec<Trigger> = db.triggers_on(&is.table, TriggerTiming::Before).into_iter().cloned().collect();
+        let after: Vec<Trigger> = db.triggers_on(&is.table, TriggerTiming::After).into_iter().cloned().collect();
+        if self.trigger_depth > 0 || (before.is_empty() && after.is_empty()) {
+            return self.insert_with_triggers(is, rows, &before, &after, db);
+        }
+        // what the insert and its triggers wrote is undone if any of them fails
+        let snapshot = db.snapshot_tables(&db.trigger_targets(&is.table));
+        match self.insert_with_triggers(is, rows, &before, &after, db) {
+            Err(e) => match db.restore_tables(snapshot) {
+                Ok(()) => Err(e),
+                Err(restore) => Err(Box::new(DBError::RollbackFailed(e, Box::new(restore)))),
+            },
+            result => result,
+        }
+    }
+
+    /// Stores the rows of the insert, running the BEFORE triggers for those
+    /// that are going to be written and the AFTER ones for those written
+    ///
+    /// # Errors
+    ///
+    /// Same as 'run_on_db', the writes done until the error are kept
+    fn insert_with_triggers(
+        &self,
+        is: &InsertStatement,
+        rows: Vec<Vec<DBField>>,
+        before: &[Trigger],
+        after: &[Trigger],
+        db: &mut database::db::DB,
+    ) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let Some(tb) = db.get_table(&is.table) else {
+            return Err(Box::new(EngineError::TableNotFound(is.table.clone())));
+        };
+        let header = tb.header().to_vec();
+        if !before.is_empty() {
+            let cols = is.columns.as_ref().map(|s| s.iter().map(|cs| cs.as_str()).collect());
+            let mut proposed = tb.build_rows(cols, rows.clone(), &self.eval.functions.borrow())?;
+            if let Some(oc) = &is.on_conflict {
+                proposed = tb.without_skipped(proposed, oc)?;
+            }
+            self.fire_triggers(before, &header, &proposed, db)?;
+        }
+        let (stored, result) = match db.get_mut_table(&is.table) {
+            Some(tb) => self.store_rows(tb, is, rows)?,
+            None => return Err(Box::new(EngineError::TableNotFound(is.table.clone()))),
+        };
+        self.fire_triggers(after, &header, &stored, db)?;
+        Ok(QueryResult {rows_scanned: self.rows_scanned.get(), ..result})
+    }
+
+    /// Runs the body of every trigger once per row, 'NEW.<col>' standing for
+    /// the values of the row
+    ///
+    /// # Errors
+    ///
+    /// Returns a boxed error, whatever a body failed with or an EngineError
+    /// if the triggers keep setting each other off
+    fn fire_triggers(
+        &self,
+        triggers: &[Trigger],
+        header: &[DBColumn],
+        rows: &[Vec<DBField>],
+        db: &mut database::db::DB,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for row in rows {
+            for trigger in triggers {
+                if self.trigger_depth >= MAX_TRIGGER_DEPTH {
+                    return Err(Box::new(EngineError::TriggerDepth(trigger.name().to_string(), MAX_TRIGGER_DEPTH)));
+                }
+                let body = bind_new(&trigger.definition.body, header, row)?;
+                let mut engine = Engine::new(ASTRootWrapper {first_node: ASTNode::Statment(body)});
+                engine.trigger_depth = self.trigger_depth + 1;
+                engine.run_on_db(db)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn run(&self, mut db: database::db::DB) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        self.run_on_db(&mut db)
+    }
+}
+
+/// Runs the statments of a script one after the other against the database,
+/// giving the result of each
+///
+/// # Errors
+///
+/// ScriptError with the index of the first statment that failed, the ones
+/// after it are not run
+pub fn run_script(statments: Vec<ASTRootWrapper>, db: &mut database::db::DB) -> Result<Vec<QueryResult>, ScriptError> {
+    let mut results = Vec::with_capacity(statments.len());
+    for (idx, ast_root) in statments.into_iter().enumerate() {
+        match Engine::new(ast_root).run_on_db(db) {
+            Ok(res) => results.push(res),
+            Err(e) => return Err(ScriptError::Statment(idx, e)),
+        }
     }
+    Ok(results)
 }
@@ -1,27 +1,150 @@
 use std::{fmt, error::Error};
-use crate::sql::{ast::Expr};
+use crate::sql::{Operator, ast::{Expr, SelectStatement}};
+use crate::database::{DBField, DataTypes};
 
 #[derive(Debug)]
 pub enum EngineError {
     UnexpectedExprExpectedLiteral(Expr),
     UnexpectedExprExpectedExpression(Expr),
+    TableNotFound(String),
+    ColumnNotFound(String),
+    TypeMismatch(Operator, DBField, DBField),
+    ArithmeticOverflow(Operator, DBField, DBField),
+    DivisionByZero,
+    SubqueryColumnCount(usize),
+    SubqueryNotMaterialized(Box<SelectStatement>),
+    UnknownFunction(String),
+    FunctionArity(String, usize),
+    FunctionArgumentType(String, DBField),
+    InvalidCast(DBField, DataTypes),
+    FunctionFailed(String, String),
+    AggregateMisuse(String),
+    StarArgument(String),
+    UngroupedColumn(String),
+    WindowMisuse(String),
+    NotAWindowFunction(String),
+    CompoundColumnCount(usize, usize),
+    CompoundTypeMismatch(String, DataTypes, DataTypes),
+    CteColumnCount(String, usize, usize),
+    RecursiveCteOperator(String),
+    RecursionLimit(String, usize),
+    ViewCycle(String),
+    ViewNotWritable(String),
+    NeedsDatabase(String),
+    NotReadOnly(String),
+    TriggerDepth(String, usize),
+    UnboundParameter(String),
+    ParameterCount(usize, usize),
+    ParameterType(String, DBField, DataTypes),
     UnexpectedState,
 }
 impl fmt::Display for EngineError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", match self {
-            EngineError::UnexpectedExprExpectedLiteral(_expr) => {
-                let out_s: String = "Invalid expression found on the right of a where statment, expected 'literal' found '".to_string();
-                // TODO add proper string method on expr
-                out_s
+            EngineError::UnexpectedExprExpectedLiteral(expr) => {
+                format!("Invalid expression found on the right of a where statment, expected 'literal' found '{}'", expr)
             }
-            EngineError::UnexpectedExprExpectedExpression(_expr) => {
-                let out_s: String = "Invalid expression found in a where statment, expected 'Expression' found '".to_string();
-                // TODO add proper string method on expr
-                out_s
+            EngineError::UnexpectedExprExpectedExpression(expr) => {
+                format!("Invalid expression found in a where statment, expected 'Expression' found '{}'", expr)
+            },
+            EngineError::TableNotFound(name) => format!("table '{}' not found", name),
+            EngineError::ColumnNotFound(name) => format!("column '{}' not found", name),
+            EngineError::TypeMismatch(op, l, r) => {
+                format!("can not apply '{}' to '{}' and '{}'", op, l.to_file_string(), r.to_file_string())
+            },
+            EngineError::ArithmeticOverflow(op, l, r) => {
+                format!("overflow computing '{} {} {}'", l.to_file_string(), op, r.to_file_string())
+            },
+            EngineError::DivisionByZero => "division by zero".to_string(),
+            EngineError::SubqueryColumnCount(n) => format!("subquery returns {} columns, expected 1", n),
+            EngineError::SubqueryNotMaterialized(sq) => {
+                format!("subquery on table '{}' was not run before being used", sq.table)
+            },
+            EngineError::UnknownFunction(name) => format!("unknown function '{}'", name),
+            EngineError::FunctionArity(name, n) => format!("function '{}' does not take {} arguments", name, n),
+            EngineError::FunctionArgumentType(name, f) => {
+                format!("invalid argument '{}' for function '{}'", f.to_file_string(), name)
+            },
+            EngineError::InvalidCast(f, t) => format!("can not cast '{}' to {}", f.to_file_string(), t.to_file_string()),
+            EngineError::FunctionFailed(name, e) => format!("function '{}' failed: {}", name, e),
+            EngineError::AggregateMisuse(name) => {
+                format!("aggregate function '{}' may only be used in the selected columns", name)
+            },
+            EngineError::StarArgument(name) => format!("only aggregate functions take '*', not '{}'", name),
+            EngineError::UngroupedColumn(name) => {
+                format!("column '{}' must be inside an aggregate, there is no GROUP BY", name)
+            },
+            EngineError::WindowMisuse(name) => {
+                format!("window function '{}' may only be used in the selected columns", name)
+            },
+            EngineError::NotAWindowFunction(name) => format!("'{}' can not be used with OVER", name),
+            EngineError::CompoundColumnCount(expected, got) => {
+                format!("compound select parts return {} and {} columns", expected, got)
+            },
+            EngineError::CompoundTypeMismatch(col, l, r) => {
+                format!("column '{}' mixes {} and {} across the compound select", col, l.to_file_string(), r.to_file_string())
+            },
+            EngineError::CteColumnCount(name, expected, got) => {
+                format!("CTE '{}' names {} columns but its query returns {}", name, expected, got)
+            },
+            EngineError::RecursiveCteOperator(name) => {
+                format!("recursive CTE '{}' may only combine its selects with UNION [ALL]", name)
+            },
+            EngineError::RecursionLimit(name, limit) => {
+                format!("recursive CTE '{}' did not end within {} rounds", name, limit)
+            },
+            EngineError::ViewCycle(name) => format!("view '{}' ends up reading from itself", name),
+            EngineError::ViewNotWritable(name) => format!("can not insert into view '{}'", name),
+            EngineError::NeedsDatabase(statment) => format!("'{}' can only be run against a database", statment),
+            EngineError::NotReadOnly(statment) => format!("'{}' changes the database, it can not be run as a query", statment),
+            EngineError::UnboundParameter(p) => format!("no value was bound to parameter '{}'", p),
+            EngineError::ParameterCount(expected, got) => {
+                format!("wrong number of parameter values. Got {} expected {}", got, expected)
+            },
+            EngineError::ParameterType(p, f, t) => {
+                format!("parameter '{}' is compared to or stored in a {} column, got '{}'", p, t.to_file_string(), f.to_file_string())
+            },
+            EngineError::TriggerDepth(name, max) => {
+                format!("trigger '{}' set off more than {} nested triggers", name, max)
             },
             EngineError::UnexpectedState => "unexpected state encoutered".to_string(),
         })
     }
 }
 impl Error for EngineError {}
+
+/// A statment of sql that failed to parse
+#[derive(Debug)]
+pub struct ParseError(pub String);
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl Error for ParseError {}
+
+/// The failure of a script, statments being counted from 0. Those before the
+/// failing one have been run, those after it have not.
+#[derive(Debug)]
+pub enum ScriptError {
+    Lexing(String),
+    Statment(usize, Box<dyn Error>),
+}
+impl ScriptError {
+    /// The index of the failing statment, None if the script could not be lexed
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            ScriptError::Lexing(_) => None,
+            ScriptError::Statment(idx, _) => Some(*idx),
+        }
+    }
+}
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            ScriptError::Lexing(e) => format!("failed to lex the script: {}", e),
+            ScriptError::Statment(idx, e) => format!("statment {} failed: {}", idx, e),
+        })
+    }
+}
+impl Error for ScriptError {}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use crate::database::{DBField, DataTypes};
+use crate::sql::engine::EngineResult;
+use crate::sql::errors::EngineError;
+
+/*  ## Functions ##
+ *  The built-in scalar functions callable from sql, looked up by their
+ *  upper-cased name. Unless stated otherwise a NULL argument makes the result NULL.
+ *
+ *  Next to those the FunctionRegistry holds the functions registered from rust,
+ *  these take precedence over the built-ins of the same name.
+ */
+
+pub type ScalarFn = fn(&[DBField]) -> EngineResult<DBField>;
+
+pub type UserScalarFn = Arc<dyn Fn(&[DBField]) -> Result<DBField, String> + Send + Sync>;
+
+/// The running state of one evaluation of an aggregate function
+pub trait AggregateState {
+    fn step(&mut self, args: &[DBField]) -> Result<(), String>;
+    fn finalize(self: Box<Self>) -> Result<DBField, String>;
+}
+
+type StepFn<S> = Arc<dyn Fn(&mut S, &[DBField]) -> Result<(), String> + Send + Sync>;
+type FinalizeFn<S> = Arc<dyn Fn(S) -> Result<DBField, String> + Send + Sync>;
+type AggregateFactory = Arc<dyn Fn() -> Box<dyn AggregateState> + Send + Sync>;
+
+struct ClosureAggregate<S> {
+    state: S,
+    step: StepFn<S>,
+    finalize: FinalizeFn<S>,
+}
+
+impl<S> AggregateState for ClosureAggregate<S> {
+    fn step(&mut self, args: &[DBField]) -> Result<(), String> {
+        (self.step)(&mut self.state, args)
+    }
+    fn finalize(self: Box<Self>) -> Result<DBField, String> {
+        (self.finalize)(self.state)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    scalars: HashMap<String, UserScalarFn>,
+    aggregates: HashMap<String, AggregateFactory>,
+    /// the type every registered function declares for its results
+    returns: HashMap<String, DataTypes>,
+}
+
+impl FunctionRegistry {
+    /// Registers a scalar function, its results being of type 'returns' or NULL
+    pub fn register_scalar(
+        &mut self,
+        name: &str,
+        returns: DataTypes,
+        f: impl Fn(&[DBField]) -> Result<DBField, String> + Send + Sync + 'static,
+    ) {
+        let checked = returns.clone();
+        self.scalars.insert(name.to_uppercase(), Arc::new(move |args| f(args).and_then(|v| check_return(&checked, v))));
+        self.returns.insert(name.to_uppercase(), returns);
+    }
+
+    /// Registers an aggregate, every evaluation starting from 'init()', feeding
+    /// each row's arguments to 'step' and producing its value, of type 'returns'
+    /// or NULL, with 'finalize'
+    pub fn register_aggregate<S: 'static>(
+        &mut self,
+        name: &str,
+        returns: DataTypes,
+        init: impl Fn() -> S + Send + Sync + 'static,
+        step: impl Fn(&mut S, &[DBField]) -> Result<(), String> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<DBField, String> + Send + Sync + 'static,
+    ) {
+        let checked = returns.clone();
+        self.returns.insert(name.to_uppercase(), returns);
+        let step: StepFn<S> = Arc::new(step);
+        let finalize: FinalizeFn<S> = Arc::new(move |state| finalize(state).and_then(|v| check_return(&checked, v)));
+        let factory: AggregateFactory = Arc::new(move || {
+            Box::new(ClosureAggregate {state: init(), step: step.clone(), finalize: finalize.clone()})
+        });
+        self.aggregates.insert(name.to_uppercase(), factory);
+    }
+
+    pub fn scalar(&self, name: &str) -> Option<&UserScalarFn> {
+        self.scalars.get(name)
+    }
+
+    pub fn is_aggregate(&self, name: &str) -> bool {
+        self.aggregates.contains_key(name)
+    }
+
+    /// The type of the results of the registered function called 'name'
+    pub fn return_type(&self, name: &str) -> Option<&DataTypes> {
+        self.returns.get(name)
+    }
+
+    /// A fresh state for the aggregate called 'name'
+    pub fn start_aggregate(&self, name: &str) -> Option<Box<dyn AggregateState>> {
+        self.aggregates.get(name).map(|factory| factory())
+    }
+}
+
+impl fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FunctionRegistry")
+            .field("scalars", &self.scalars.keys().collect::<Vec<_>>())
+            .field("aggregates", &self.aggregates.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// The result of a registered function, if it is of the type it was registered with
+///
+/// # Errors
+///
+/// A message naming both types otherwise
+fn check_return(returns: &DataTypes, value: DBField) -> Result<DBField, String> {
+    match value == DBField::Null || returns.accepts(&value) {
+        true => Ok(value),
+        false => Err(format!("returned '{}', expected {}", value.to_file_string(), returns.to_file_string())),
+    }
+}
+
+pub fn builtin(name: &str) -> Option<ScalarFn> {
+    let f: ScalarFn = match name {
+        "LENGTH" => length,
+        "UPPER" => upper,
+        "LOWER" => lower,
+        "TRIM" => trim,
+        "SUBSTR" => substr,
+        "REPLACE" => replace,
+        "ABS" => abs,
+        "ROUND" => round,
+        "COALESCE" => coalesce,
+        "NULLIF" => nullif,
+        _ => return None,
+    };
+    Some(f)
+}
+
+fn check_arity(name: &str, args: &[DBField], min: usize, max: usize) -> EngineResult<()> {
+    if args.len() < min || args.len() > max {
+        return Err(EngineError::FunctionArity(name.to_string(), args.len()));
+    }
+    Ok(())
+}
+
+fn text_arg<'a>(name: &str, arg: &'a DBField) -> EngineResult<&'a str> {
+    match arg {
+        DBField::Text(s) => Ok(s),
+        f => Err(EngineError::FunctionArgumentType(name.to_string(), f.clone())),
+    }
+}
+
+fn int_arg(name: &str, arg: &DBField) -> EngineResult<i32> {
+    match arg {
+        DBField::Int(i) => Ok(*i),
+        f => Err(EngineError::FunctionArgumentType(name.to_string(), f.clone())),
+    }
+}
+
+fn length(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("LENGTH", args, 1, 1)?;
+    if args[0] == DBField::Null {return Ok(DBField::Null)}
+    let len = text_arg("LENGTH", &args[0])?.chars().count();
+    Ok(DBField::Int(len as i32))
+}
+
+fn upper(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("UPPER", args, 1, 1)?;
+    if args[0] == DBField::Null {return Ok(DBField::Null)}
+    Ok(DBField::Text(text_arg("UPPER", &args[0])?.to_uppercase()))
+}
+
+fn lower(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("LOWER", args, 1, 1)?;
+    if args[0] == DBField::Null {return Ok(DBField::Null)}
+    Ok(DBField::Text(text_arg("LOWER", &args[0])?.to_lowercase()))
+}
+
+fn trim(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("TRIM", args, 1, 1)?;
+    if args[0] == DBField::Null {return Ok(DBField::Null)}
+    Ok(DBField::Text(text_arg("TRIM", &args[0])?.trim().to_string()))
+}
+
+/// SUBSTR(text, start[, length]), 'start' counting chars from 1
+fn substr(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("SUBSTR", args, 2, 3)?;
+    if args.contains(&DBField::Null) {return Ok(DBField::Null)}
+    let s = text_arg("SUBSTR", &args[0])?;
+    let start = int_arg("SUBSTR", &args[1])?;
+    let len = match args.get(2) {
+        Some(l) => int_arg("SUBSTR", l)?.max(0) as usize,
+        None => usize::MAX,
+    };
+    let skip = (start.max(1) - 1) as usize;
+    // a start before the first char eats into the length, as in sqlite
+    let len = if start < 1 {len.saturating_sub((1 - start) as usize)} else {len};
+    Ok(DBField::Text(s.chars().skip(skip).take(len).collect()))
+}
+
+fn replace(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("REPLACE", args, 3, 3)?;
+    if args.contains(&DBField::Null) {return Ok(DBField::Null)}
+    let s = text_arg("REPLACE", &args[0])?;
+    let from = text_arg("REPLACE", &args[1])?;
+    let to = text_arg("REPLACE", &args[2])?;
+    if from.is_empty() {return Ok(DBField::Text(s.to_string()))}
+    Ok(DBField::Text(s.replace(from, to)))
+}
+
+fn abs(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("ABS", args, 1, 1)?;
+    if args[0] == DBField::Null {return Ok(DBField::Null)}
+    let i = int_arg("ABS", &args[0])?;
+    match i.checked_abs() {
+        Some(a) => Ok(DBField::Int(a)),
+        None => Err(EngineError::FunctionArgumentType("ABS".to_string(), args[0].clone())),
+    }
+}
+
+/// ROUND(int[, digits]), a negative 'digits' rounding to tens, hundreds...
+/// half away from zero. INTs have no fraction so positive digits change nothing.
+fn round(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("ROUND", args, 1, 2)?;
+    if args.contains(&DBField::Null) {return Ok(DBField::Null)}
+    let i = int_arg("ROUND", &args[0])?;
+    let digits = match args.get(1) {
+        Some(d) => int_arg("ROUND", d)?,
+        None => 0,
+    };
+    if digits >= 0 {return Ok(DBField::Int(i))}
+    let unit = match 10i64.checked_pow(digits.unsigned_abs()) {
+        Some(u) => u,
+        None => return Ok(DBField::Int(0)),
+    };
+    let i = i as i64;
+    let rounded = (i.abs() + unit / 2) / unit * unit * i.signum();
+    match i32::try_from(rounded) {
+        Ok(r) => Ok(DBField::Int(r)),
+        Err(_) => Err(EngineError::FunctionArgumentType("ROUND".to_string(), args[0].clone())),
+    }
+}
+
+/// The first argument that is not NULL, NULL if there is none
+fn coalesce(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("COALESCE", args, 1, usize::MAX)?;
+    Ok(args.iter().find(|f| **f != DBField::Null).cloned().unwrap_or(DBField::Null))
+}
+
+/// NULL if both arguments are equal, the first one otherwise
+fn nullif(args: &[DBField]) -> EngineResult<DBField> {
+    check_arity("NULLIF", args, 2, 2)?;
+    if args[0] == args[1] {
+        Ok(DBField::Null)
+    } else {
+        Ok(args[0].clone())
+    }
+}
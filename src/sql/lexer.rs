@@ -5,7 +5,6 @@ use std::iter::Peekable;
 #[derive(Debug)]
 pub struct Lexer<'a> {
     pub input: Peekable<Chars<'a>>,
-    pub prev_token: SqlToken,
 }
 
 impl<'a> Lexer<'a> {
@@ -13,30 +12,87 @@ impl<'a> Lexer<'a> {
         let mut tokens = Vec::new();
         while let Some(c) = self.input.next() {
             match c {
-                ' ' | '\n' | '\t' => continue,
+                ' ' | '\n' | '\t' | '\r' => continue,
                 '(' => tokens.push(SqlToken::LeftParen),
                 ')' => tokens.push(SqlToken::RightParen),
                 ',' => tokens.push(SqlToken::Comma),
+                '.' => tokens.push(SqlToken::Dot),
+                '*' => tokens.push(SqlToken::Star),
                 ';' => tokens.push(SqlToken::Semicolon),
-                '=' => {
-                    if self.prev_token == SqlToken::Operator(Operator::NotEqual) {continue;}
-                    tokens.push(SqlToken::Operator(Operator::Equal));
+                '?' => tokens.push(SqlToken::Placeholder(Placeholder::Next)),
+                '$' => {
+                    let digits = self.lex_word();
+                    match digits.parse::<usize>() {
+                        Ok(n) if n > 0 => tokens.push(SqlToken::Placeholder(Placeholder::Index(n))),
+                        _ => return Err(format!("Invalid parameter '${}', expected a number from 1", digits)),
+                    }
+                },
+                ':' => {
+                    let name = self.lex_word();
+                    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                        return Err(format!("Invalid parameter ':{}', expected a name", name));
+                    }
+                    tokens.push(SqlToken::Placeholder(Placeholder::Name(name)));
                 },
+                '=' => tokens.push(SqlToken::Operator(Operator::Equal)),
                 '!' => {
-                    match self.input.peek() {
-                        Some(cn) => if *cn == '=' {tokens.push(SqlToken::Operator(Operator::NotEqual))}
+                    match self.input.next() {
+                        Some('=') => tokens.push(SqlToken::Operator(Operator::NotEqual)),
+                        Some(cn) => return Err(format!("Unexpected '{}' after '!', expected '='", cn)),
                         None => return Err("Unexpected '!' at the end of input".to_string()),
                     }
                 },
-                '<' => tokens.push(SqlToken::Operator(Operator::Smaller)),
-                '>' => tokens.push(SqlToken::Operator(Operator::Greater)),
-                '\'' => {
-                    let mut s = String::new();
-                    while let Some(c) = self.input.next() {
-                        if c == '\'' { break; }
-                        s.push(c);
+                '+' => tokens.push(SqlToken::Operator(Operator::Plus)),
+                // '-- comment' up to the end of the line
+                '-' if self.input.peek() == Some(&'-') => {
+                    for c in self.input.by_ref() {
+                        if c == '\n' { break; }
+                    }
+                    continue;
+                },
+                '-' => tokens.push(SqlToken::Operator(Operator::Minus)),
+                '/' if self.input.peek() == Some(&'*') => {
+                    self.input.next();
+                    self.skip_block_comment()?;
+                    continue;
+                },
+                '/' => tokens.push(SqlToken::Operator(Operator::Divide)),
+                '%' => tokens.push(SqlToken::Operator(Operator::Modulo)),
+                '|' => {
+                    match self.input.next() {
+                        Some('|') => tokens.push(SqlToken::Operator(Operator::Concat)),
+                        _ => return Err("Unexpected single '|', expected '||'".to_string()),
+                    }
+                },
+                '<' => {
+                    match self.input.peek() {
+                        Some('=') => {
+                            self.input.next();
+                            tokens.push(SqlToken::Operator(Operator::SmallerEqual));
+                        }
+                        Some('>') => {
+                            self.input.next();
+                            tokens.push(SqlToken::Operator(Operator::NotEqual));
+                        }
+                        _ => tokens.push(SqlToken::Operator(Operator::Smaller)),
+                    }
+                },
+                '>' => {
+                    if self.input.peek() == Some(&'=') {
+                        self.input.next();
+                        tokens.push(SqlToken::Operator(Operator::GreaterEqual));
+                    } else {
+                        tokens.push(SqlToken::Operator(Operator::Greater));
+                    }
+                },
+                '\'' => tokens.push(SqlToken::StringLiteral(self.lex_quoted('\'', "string literal")?)),
+                // a quoted identifier is never a keyword and may hold any char
+                '"' => {
+                    let name = self.lex_quoted('"', "quoted identifier")?;
+                    if name.is_empty() {
+                        return Err("Empty quoted identifier".to_string());
                     }
-                    tokens.push(SqlToken::StringLiteral(s));
+                    tokens.push(SqlToken::Identifier(name));
                 }
 
                 c if c.is_alphanumeric() || c == '_' => {
@@ -48,37 +104,132 @@ impl<'a> Lexer<'a> {
                         if !next.is_alphanumeric() && *next != '_' {
                             break;
                         }
-                        if next.is_ascii_digit() == false && all_num == true{
+                        if !next.is_ascii_digit() && all_num{
                             all_num = false;
                         }
                         word.push(*next);
                         self.input.next();
                     }
 
-                    let token = match word.to_uppercase().as_str() {
-                        "SELECT" => SqlToken::Keyword(SqlKeyword::Select),
-                        "FROM" => SqlToken::Keyword(SqlKeyword::From),
-                        "INSERT" => SqlToken::Keyword(SqlKeyword::Insert),
-                        "INTO" => SqlToken::Keyword(SqlKeyword::Into),
-                        "VALUES" => SqlToken::Keyword(SqlKeyword::Values),
-                        "WHERE" => SqlToken::Keyword(SqlKeyword::Where),
-                        "AND" => SqlToken::Operator(Operator::And),
-                        "OR" => SqlToken::Operator(Operator::Or),
-                        l => {if all_num {SqlToken::NumberLiteral(l.to_string())} else {SqlToken::Identifier(word)}},
+                    let token = match reserved_word(&word) {
+                        Some(token) => token,
+                        None if all_num => SqlToken::NumberLiteral(word),
+                        None => SqlToken::Identifier(word),
                     };
 
                     tokens.push(token);
                 }
                 _ => return Err(format!("Unexpected char: {}", c)),
             }
-            self.prev_token = match tokens.last() {
-                Some(tkn) => tkn.clone(),
-                _ => SqlToken::EOF,
-            }
         }
 
         tokens.push(SqlToken::EOF);
         Ok(tokens)
     }
+
+    /// Reads the letters, digits and underscores that follow
+    fn lex_word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(next) = self.input.peek() {
+            if !next.is_alphanumeric() && *next != '_' {
+                break;
+            }
+            word.push(*next);
+            self.input.next();
+        }
+        word
+    }
+
+    /// Reads up to the closing 'quote', the opening one being consumed
+    /// already. A doubled quote stands for the quote itself.
+    fn lex_quoted(&mut self, quote: char, what: &str) -> Result<String, String> {
+        let mut s = String::new();
+        loop {
+            match self.input.next() {
+                Some(c) if c == quote => {
+                    if self.input.peek() != Some(&quote) {
+                        return Ok(s);
+                    }
+                    self.input.next();
+                    s.push(quote);
+                }
+                Some(c) => s.push(c),
+                None => return Err(format!("Unterminated {}, missing the closing {}", what, quote)),
+            }
+        }
+    }
+
+    /// Skips a '/* */' comment, the opening '/*' being consumed already
+    fn skip_block_comment(&mut self) -> Result<(), String> {
+        while let Some(c) = self.input.next() {
+            if c == '*' && self.input.peek() == Some(&'/') {
+                self.input.next();
+                return Ok(());
+            }
+        }
+        Err("Unterminated block comment, missing the closing */".to_string())
+    }
 }
 
+/// The token of a keyword or of a word standing for an operator, None for
+/// the other words, case insensitive
+pub fn reserved_word(word: &str) -> Option<SqlToken> {
+    Some(match word.to_uppercase().as_str() {
+        "SELECT" => SqlToken::Keyword(SqlKeyword::Select),
+        "FROM" => SqlToken::Keyword(SqlKeyword::From),
+        "INSERT" => SqlToken::Keyword(SqlKeyword::Insert),
+        "INTO" => SqlToken::Keyword(SqlKeyword::Into),
+        "VALUES" => SqlToken::Keyword(SqlKeyword::Values),
+        "WHERE" => SqlToken::Keyword(SqlKeyword::Where),
+        "ON" => SqlToken::Keyword(SqlKeyword::On),
+        "CONFLICT" => SqlToken::Keyword(SqlKeyword::Conflict),
+        "DO" => SqlToken::Keyword(SqlKeyword::Do),
+        "NOTHING" => SqlToken::Keyword(SqlKeyword::Nothing),
+        "UPDATE" => SqlToken::Keyword(SqlKeyword::Update),
+        "SET" => SqlToken::Keyword(SqlKeyword::Set),
+        "RETURNING" => SqlToken::Keyword(SqlKeyword::Returning),
+        "AND" => SqlToken::Operator(Operator::And),
+        "OR" => SqlToken::Operator(Operator::Or),
+        "NOT" => SqlToken::Operator(Operator::Not),
+        "LIKE" => SqlToken::Operator(Operator::Like(None)),
+        "ILIKE" => SqlToken::Operator(Operator::ILike(None)),
+        "GLOB" => SqlToken::Operator(Operator::Glob),
+        "ESCAPE" => SqlToken::Keyword(SqlKeyword::Escape),
+        "IN" => SqlToken::Keyword(SqlKeyword::In),
+        "BETWEEN" => SqlToken::Keyword(SqlKeyword::Between),
+        "EXISTS" => SqlToken::Keyword(SqlKeyword::Exists),
+        "NULL" => SqlToken::Keyword(SqlKeyword::Null),
+        "CAST" => SqlToken::Keyword(SqlKeyword::Cast),
+        "AS" => SqlToken::Keyword(SqlKeyword::As),
+        "CASE" => SqlToken::Keyword(SqlKeyword::Case),
+        "WHEN" => SqlToken::Keyword(SqlKeyword::When),
+        "THEN" => SqlToken::Keyword(SqlKeyword::Then),
+        "ELSE" => SqlToken::Keyword(SqlKeyword::Else),
+        "END" => SqlToken::Keyword(SqlKeyword::End),
+        "DISTINCT" => SqlToken::Keyword(SqlKeyword::Distinct),
+        "UNION" => SqlToken::Keyword(SqlKeyword::Union),
+        "ALL" => SqlToken::Keyword(SqlKeyword::All),
+        "INTERSECT" => SqlToken::Keyword(SqlKeyword::Intersect),
+        "EXCEPT" => SqlToken::Keyword(SqlKeyword::Except),
+        "WITH" => SqlToken::Keyword(SqlKeyword::With),
+        "RECURSIVE" => SqlToken::Keyword(SqlKeyword::Recursive),
+        "OVER" => SqlToken::Keyword(SqlKeyword::Over),
+        "PARTITION" => SqlToken::Keyword(SqlKeyword::Partition),
+        "ORDER" => SqlToken::Keyword(SqlKeyword::Order),
+        "BY" => SqlToken::Keyword(SqlKeyword::By),
+        "ASC" => SqlToken::Keyword(SqlKeyword::Asc),
+        "DESC" => SqlToken::Keyword(SqlKeyword::Desc),
+        "CREATE" => SqlToken::Keyword(SqlKeyword::Create),
+        "VIEW" => SqlToken::Keyword(SqlKeyword::View),
+        "DROP" => SqlToken::Keyword(SqlKeyword::Drop),
+        "MATERIALIZED" => SqlToken::Keyword(SqlKeyword::Materialized),
+        "REFRESH" => SqlToken::Keyword(SqlKeyword::Refresh),
+        "TRIGGER" => SqlToken::Keyword(SqlKeyword::Trigger),
+        "BEFORE" => SqlToken::Keyword(SqlKeyword::Before),
+        "AFTER" => SqlToken::Keyword(SqlKeyword::After),
+        "FOR" => SqlToken::Keyword(SqlKeyword::For),
+        "EACH" => SqlToken::Keyword(SqlKeyword::Each),
+        "ROW" => SqlToken::Keyword(SqlKeyword::Row),
+        _ => return None,
+    })
+}
@@ -3,6 +3,10 @@ pub mod parser;
 pub mod ast;
 pub mod engine;
 pub mod errors;
+pub mod pattern;
+pub mod functions;
+pub mod window;
+pub mod prepared;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SqlToken {
@@ -12,7 +16,10 @@ pub enum SqlToken {
     NumberLiteral(String),
     Operator(Operator),
     Comma,
+    Dot,
+    Star,
     Semicolon,
+    Placeholder(Placeholder),
     LeftParen,
     RightParen,
     EOF,
@@ -26,6 +33,58 @@ pub enum SqlKeyword {
     Into,
     Values,
     Where,
+    On,
+    Conflict,
+    Do,
+    Nothing,
+    Update,
+    Set,
+    Returning,
+    Escape,
+    In,
+    Between,
+    Exists,
+    Null,
+    Cast,
+    As,
+    Case,
+    When,
+    Then,
+    Else,
+    End,
+    Distinct,
+    Union,
+    All,
+    Intersect,
+    Except,
+    With,
+    Recursive,
+    Over,
+    Partition,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Create,
+    View,
+    Drop,
+    Materialized,
+    Refresh,
+    Trigger,
+    Before,
+    After,
+    For,
+    Each,
+    Row,
+}
+
+/// '?', '$n' or ':name', standing for a value bound when the statment is run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Placeholder {
+    /// '?', numbered after the highest numbered placeholder before it
+    Next,
+    Index(usize),
+    Name(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -34,24 +93,43 @@ pub enum Operator {
     NotEqual,
     Greater,
     Smaller,
+    GreaterEqual,
+    SmallerEqual,
+    Like(Option<char>),
+    ILike(Option<char>),
+    Glob,
     And,
     Or,
+    Not,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
 }
 
-/*
-impl Operator {
-    fn to_string(&self) -> String {
-        match self {
-            Operator::Equal => "Equal".to_string(),
-            Operator::NotEqual => "Not Equal".to_string(),
-            Operator::Greater => "Greater".to_string(),
-            Operator::Smaller => "Smaller".to_string(),
-            Operator::And => "And".to_string(),
-            Operator::Or => "Or".to_string(),
-        }
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Operator::Equal => "=",
+            Operator::NotEqual => "!=",
+            Operator::Greater => ">",
+            Operator::Smaller => "<",
+            Operator::GreaterEqual => ">=",
+            Operator::SmallerEqual => "<=",
+            Operator::Like(_) => "LIKE",
+            Operator::ILike(_) => "ILIKE",
+            Operator::Glob => "GLOB",
+            Operator::And => "AND",
+            Operator::Or => "OR",
+            Operator::Not => "NOT",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Concat => "||",
+        })
     }
 }
-*/
-
-
-
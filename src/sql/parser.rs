@@ -1,15 +1,23 @@
 use crate::sql::*;
 use crate::sql::ast::*;
+use crate::sql::lexer::Lexer;
+use crate::sql::errors::{ParseError, ScriptError};
+use crate::database::{ColumnConstraint, DataTypes, GeneratedColumn};
+
+/// precedence of the comparison operators, also that of IN, BETWEEN and LIKE
+const COMPARISON_PREC: u8 = 4;
 
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<SqlToken>,
     pos: usize,
+    /// the highest number given to a placeholder of the current statment
+    last_parameter: usize,
 }
 
 impl Parser {
     pub fn new(vec: Vec<SqlToken>) -> Self {
-        Parser {tokens: vec, pos: 0}
+        Parser {tokens: vec, pos: 0, last_parameter: 0}
     }
     fn current(&self) -> &SqlToken {
         &self.tokens[self.pos]
@@ -18,47 +26,156 @@ impl Parser {
     fn advance(&mut self) {
         self.pos += 1;
     }
-    fn parse_comparison(&mut self) -> Result<Expr, String> {
-        let left = self.parse_primary()?;
+    /// The binary operator at the current token along with its precedence,
+    /// a higher precedence binding tighter
+    fn current_binary_op(&self) -> Option<(Operator, u8)> {
+        let op = match self.current() {
+            // '*' is lexed as a star since it also stands for "all columns"
+            SqlToken::Star => Operator::Multiply,
+            SqlToken::Operator(op) => op.clone(),
+            _ => return None,
+        };
+        let prec = match op {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal
+                | Operator::NotEqual
+                | Operator::Greater
+                | Operator::Smaller
+                | Operator::GreaterEqual
+                | Operator::SmallerEqual
+                | Operator::Like(_)
+                | Operator::ILike(_)
+                | Operator::Glob => COMPARISON_PREC,
+            Operator::Concat => 5,
+            Operator::Plus | Operator::Minus => 6,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 7,
+            Operator::Not => return None,
+        };
+        Some((op, prec))
+    }
 
-        if let SqlToken::Operator(op @ (
-                Operator::Equal |
-                Operator::NotEqual |
-                Operator::Greater |
-                Operator::Smaller
-        )) = self.current() {
-            let op = op.clone();
-            self.advance();
-            let right = self.parse_primary()?;
+    /// Precedence climbing over the binary operators, all of them being left
+    /// associative. NOT sits between AND and the comparisons.
+    fn parse_binary(&mut self, min_prec: u8) -> Result<Expr, String> {
+        let mut left = self.parse_unary()?;
 
-            Ok(Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            })
-        } else {
-            Ok(left)
+        loop {
+            // 'a NOT LIKE b' is read as 'NOT (a LIKE b)', the same for IN and BETWEEN
+            let negated = *self.current() == SqlToken::Operator(Operator::Not) && matches!(
+                self.tokens.get(self.pos + 1),
+                Some(SqlToken::Operator(Operator::Like(_) | Operator::ILike(_) | Operator::Glob))
+                    | Some(SqlToken::Keyword(SqlKeyword::In | SqlKeyword::Between))
+            );
+            if negated {
+                if min_prec > COMPARISON_PREC {break}
+                self.advance();
+            }
+            left = match self.current() {
+                SqlToken::Keyword(SqlKeyword::In) if min_prec <= COMPARISON_PREC => self.parse_in(left)?,
+                SqlToken::Keyword(SqlKeyword::Between) if min_prec <= COMPARISON_PREC => self.parse_between(left)?,
+                _ => {
+                    let (mut op, prec) = match self.current_binary_op() {
+                        Some(o) => o,
+                        None => break,
+                    };
+                    if prec < min_prec {break}
+                    self.advance();
+                    let right = self.parse_binary(prec + 1)?;
+                    if let Operator::Like(escape) | Operator::ILike(escape) = &mut op {
+                        *escape = self.parse_escape()?;
+                    }
+                    Expr::Binary {
+                        left: Box::new(left),
+                        op,
+                        right: Box::new(right),
+                    }
+                }
+            };
+            if negated {
+                left = Expr::Unary {
+                    op: Operator::Not,
+                    expr: Box::new(left),
+                };
+            }
         }
+
+        Ok(left)
     }
 
-    fn parse_expr(&mut self) -> Result<Expr, String> {
-        let mut left = self.parse_comparison()?;
+    fn parse_in(&mut self, left: Expr) -> Result<Expr, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::In))?;
+        self.expect(SqlToken::LeftParen)?;
+        let expr = if *self.current() == SqlToken::Keyword(SqlKeyword::Select) {
+            Expr::InSubquery {
+                expr: Box::new(left),
+                subquery: Box::new(self.parse_select()?),
+            }
+        } else {
+            Expr::InList {
+                expr: Box::new(left),
+                list: self.parse_comma_separated(|p| p.parse_expr())?,
+            }
+        };
+        self.expect(SqlToken::RightParen)?;
+        Ok(expr)
+    }
 
-        while let SqlToken::Operator(op @ (Operator::And | Operator::Or)) = self.current() {
-            let op = op.clone();
-            self.advance();
-            let right = self.parse_comparison()?;
+    fn parse_between(&mut self, left: Expr) -> Result<Expr, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Between))?;
+        let low = self.parse_binary(COMPARISON_PREC + 1)?;
+        self.expect(SqlToken::Operator(Operator::And))?;
+        let high = self.parse_binary(COMPARISON_PREC + 1)?;
+        Ok(Expr::Between {
+            expr: Box::new(left),
+            low: Box::new(low),
+            high: Box::new(high),
+        })
+    }
 
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+    fn parse_escape(&mut self) -> Result<Option<char>, String> {
+        if *self.current() != SqlToken::Keyword(SqlKeyword::Escape) {
+            return Ok(None);
         }
+        self.advance();
+        let escape = match self.current() {
+            SqlToken::StringLiteral(s) if s.chars().count() == 1 => s.chars().next(),
+            token => return Err(format!("Expected a single character after ESCAPE, found {:?}", token)),
+        };
+        self.advance();
+        Ok(escape)
+    }
 
-        Ok(left)
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        self.parse_binary(1)
     }
 
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.current() {
+            SqlToken::Operator(Operator::Not) => {
+                self.advance();
+                Ok(Expr::Unary {
+                    op: Operator::Not,
+                    expr: Box::new(self.parse_binary(COMPARISON_PREC)?),
+                })
+            }
+            SqlToken::Operator(Operator::Minus) => {
+                self.advance();
+                // folding the sign into the literal, so that i32::MIN can be written
+                if let SqlToken::NumberLiteral(n) = self.current() {
+                    let value = format!("-{}", n).parse::<i32>()
+                        .map_err(|_| format!("Invalid number literal: -{}", n))?;
+                    self.advance();
+                    return Ok(Expr::Literal(ast::Literal::Number(value)));
+                }
+                Ok(Expr::Unary {
+                    op: Operator::Minus,
+                    expr: Box::new(self.parse_unary()?),
+                })
+            }
+            _ => self.parse_primary(),
+        }
+    }
 
     fn expect(&mut self, expected: SqlToken) -> Result<(), String> {
         if *self.current() == expected {
@@ -90,6 +207,140 @@ impl Parser {
         }
     }
 
+    /// Parses an optional 'AS alias', the 'AS' itself may be left out
+    fn parse_alias(&mut self) -> Result<Option<String>, String> {
+        match self.current() {
+            SqlToken::Keyword(SqlKeyword::As) => {
+                self.advance();
+                Ok(Some(self.parse_identifier()?))
+            }
+            SqlToken::Identifier(_) => Ok(Some(self.parse_identifier()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses the 'OVER ([PARTITION BY expr, ...] [ORDER BY expr [ASC|DESC], ...])'
+    /// following the call of a window function
+    fn parse_window(&mut self, name: String, args: Vec<Expr>, star: bool) -> Result<Expr, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Over))?;
+        self.expect(SqlToken::LeftParen)?;
+        let mut partition_by = vec![];
+        if *self.current() == SqlToken::Keyword(SqlKeyword::Partition) {
+            self.advance();
+            self.expect(SqlToken::Keyword(SqlKeyword::By))?;
+            partition_by = self.parse_comma_separated(|p| p.parse_expr())?;
+        }
+        let mut order_by = vec![];
+        if *self.current() == SqlToken::Keyword(SqlKeyword::Order) {
+            self.advance();
+            self.expect(SqlToken::Keyword(SqlKeyword::By))?;
+            order_by = self.parse_comma_separated(|p| p.parse_order_by_item())?;
+        }
+        self.expect(SqlToken::RightParen)?;
+        Ok(Expr::Window {name, args, star, partition_by, order_by})
+    }
+
+    fn parse_order_by_item(&mut self) -> Result<OrderByItem, String> {
+        let expr = self.parse_expr()?;
+        let descending = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Asc) => {
+                self.advance();
+                false
+            }
+            SqlToken::Keyword(SqlKeyword::Desc) => {
+                self.advance();
+                true
+            }
+            _ => false,
+        };
+        Ok(OrderByItem {expr, descending})
+    }
+
+    fn parse_case(&mut self) -> Result<Expr, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Case))?;
+        let operand = if *self.current() == SqlToken::Keyword(SqlKeyword::When) {
+            None
+        } else {
+            Some(Box::new(self.parse_expr()?))
+        };
+        let mut branches = vec![];
+        while *self.current() == SqlToken::Keyword(SqlKeyword::When) {
+            self.advance();
+            let cond = self.parse_expr()?;
+            self.expect(SqlToken::Keyword(SqlKeyword::Then))?;
+            branches.push((cond, self.parse_expr()?));
+        }
+        if branches.is_empty() {
+            return Err(format!("Expected WHEN, found {:?}", self.current()));
+        }
+        let else_result = if *self.current() == SqlToken::Keyword(SqlKeyword::Else) {
+            self.advance();
+            Some(Box::new(self.parse_expr()?))
+        } else {
+            None
+        };
+        self.expect(SqlToken::Keyword(SqlKeyword::End))?;
+        Ok(Expr::Case {operand, branches, else_result})
+    }
+
+    fn parse_data_type(&mut self) -> Result<DataTypes, String> {
+        let name = self.parse_identifier()?;
+        match name.to_uppercase().as_str() {
+            "INT" | "INTEGER" => Ok(DataTypes::INT),
+            "TEXT" => Ok(DataTypes::TEXT),
+            _ => Err(format!("Unknown data type: {}", name)),
+        }
+    }
+
+    /// Whether the current token is the unquoted or quoted word 'word',
+    /// the words of column definitions not being keywords
+    fn current_is_word(&self, word: &str) -> bool {
+        matches!(self.current(), SqlToken::Identifier(w) if w.eq_ignore_ascii_case(word))
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), String> {
+        if !self.current_is_word(word) {
+            return Err(format!("Expected {}, found {:?}", word, self.current()));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    /// Parses a column of a table header after its name,
+    /// 'TYPE [NULL] [UNIQUE|PRIMARY KEY] [GENERATED ALWAYS AS (expr) [STORED|VIRTUAL]]'
+    fn parse_column_def(&mut self) -> Result<ColumnDef, String> {
+        let dt_type = self.parse_data_type()?;
+        let nullable = *self.current() == SqlToken::Keyword(SqlKeyword::Null);
+        if nullable {
+            self.advance();
+        }
+        let constraint = if self.current_is_word("UNIQUE") {
+            self.advance();
+            Some(ColumnConstraint::Unique)
+        } else if self.current_is_word("PRIMARY") {
+            self.advance();
+            self.expect_word("KEY")?;
+            Some(ColumnConstraint::PrimaryKey)
+        } else {
+            None
+        };
+        let mut generated = None;
+        if self.current_is_word("GENERATED") {
+            self.advance();
+            self.expect_word("ALWAYS")?;
+            self.expect(SqlToken::Keyword(SqlKeyword::As))?;
+            self.expect(SqlToken::LeftParen)?;
+            let expr = self.parse_expr()?;
+            self.expect(SqlToken::RightParen)?;
+            let stored = self.current_is_word("STORED");
+            if stored || self.current_is_word("VIRTUAL") {
+                self.advance();
+            }
+            generated = Some(GeneratedColumn {expr, stored});
+        }
+        Ok(ColumnDef {dt_type, nullable, constraint, generated})
+    }
+
     fn parse_primary(&mut self) -> Result<Expr, String> {
        match self.current() {
             SqlToken::StringLiteral(s) => {
@@ -97,6 +348,18 @@ impl Parser {
                 self.advance();
                 Ok(expr)
             }
+            SqlToken::Placeholder(p) => {
+                let parameter = match p {
+                    Placeholder::Next => Parameter::Index(self.last_parameter + 1),
+                    Placeholder::Index(n) => Parameter::Index(*n),
+                    Placeholder::Name(name) => Parameter::Name(name.clone()),
+                };
+                if let Parameter::Index(n) = parameter {
+                    self.last_parameter = self.last_parameter.max(n);
+                }
+                self.advance();
+                Ok(Expr::Parameter(parameter))
+            }
             SqlToken::NumberLiteral(n) => {
                 let value = n.parse::<i32>()
                     .map_err(|_| format!("Invalid number literal: {}", n))?;
@@ -104,8 +367,57 @@ impl Parser {
                 Ok(Expr::Literal(ast::Literal::Number(value)))
             }
             SqlToken::Identifier(name) => {
-                let expr = Expr::Identifier(name.clone());
+                let name = name.clone();
+                self.advance();
+                if *self.current() == SqlToken::LeftParen {
+                    self.advance();
+                    // 'f(*)', as in COUNT(*), is a call without arguments
+                    let star = *self.current() == SqlToken::Star;
+                    if star {
+                        self.advance();
+                    }
+                    let args = if star || *self.current() == SqlToken::RightParen {
+                        vec![]
+                    } else {
+                        self.parse_comma_separated(|p| p.parse_expr())?
+                    };
+                    self.expect(SqlToken::RightParen)?;
+                    if *self.current() == SqlToken::Keyword(SqlKeyword::Over) {
+                        return self.parse_window(name.to_uppercase(), args, star);
+                    }
+                    return Ok(Expr::Function {name: name.to_uppercase(), args, star});
+                }
+                if *self.current() == SqlToken::Dot {
+                    self.advance();
+                    return Ok(Expr::QualifiedIdentifier {qualifier: name, name: self.parse_identifier()?});
+                }
+                Ok(Expr::Identifier(name))
+            }
+            SqlToken::Keyword(SqlKeyword::Null) => {
+                self.advance();
+                Ok(Expr::Literal(ast::Literal::Null))
+            }
+            SqlToken::Keyword(SqlKeyword::Cast) => {
+                self.advance();
+                self.expect(SqlToken::LeftParen)?;
+                let expr = self.parse_expr()?;
+                self.expect(SqlToken::Keyword(SqlKeyword::As))?;
+                let data_type = self.parse_data_type()?;
+                self.expect(SqlToken::RightParen)?;
+                Ok(Expr::Cast {expr: Box::new(expr), data_type})
+            }
+            SqlToken::Keyword(SqlKeyword::Case) => self.parse_case(),
+            SqlToken::Keyword(SqlKeyword::Exists) => {
+                self.advance();
+                self.expect(SqlToken::LeftParen)?;
+                let subquery = self.parse_select()?;
+                self.expect(SqlToken::RightParen)?;
+                Ok(Expr::Exists(Box::new(subquery)))
+            }
+            SqlToken::LeftParen => {
                 self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(SqlToken::RightParen)?;
                 Ok(expr)
             }
             token => Err(format!("Expected expression, found {:?}", token)),
@@ -114,37 +426,187 @@ impl Parser {
 
     fn parse_select(&mut self) -> Result<SelectStatement, String> {
         self.expect(SqlToken::Keyword(SqlKeyword::Select))?;
+        let distinct = *self.current() == SqlToken::Keyword(SqlKeyword::Distinct);
+        if distinct {
+            self.advance();
+        }
 
-        let columns = self.parse_comma_separated(|p| p.parse_identifier())?;
+        let columns = self.parse_comma_separated(|p| {
+            let expr = p.parse_expr()?;
+            let alias = p.parse_alias()?;
+            Ok(SelectItem {expr, alias})
+        })?;
         self.expect(SqlToken::Keyword(SqlKeyword::From))?;
 
         let table = self.parse_identifier()?;
+        let table_alias = self.parse_alias()?;
         let mut where_clauses: Vec<Expr> = vec![];
         loop {
             let where_clause = if self.current() == &SqlToken::Keyword(SqlKeyword::Where) ||
                 self.current() == &SqlToken::Operator(Operator::And) {
                     self.advance(); 
-                    match self.parse_expr() {
-                        Ok(wc) => wc,
-                        Err(_s) => break,
-                    }
+                    self.parse_expr()?
                 } else {
                     break;
             };
             where_clauses.push(where_clause);
         }
-        let where_clauses = if where_clauses.len() < 1 {
+        let where_clauses = if where_clauses.is_empty() {
             Option::None
         } else {
             Option::Some(where_clauses)
         };
 
         Ok(SelectStatement {
+            distinct,
             columns,
             table,
+            table_alias,
             where_clause: where_clauses,
         })
     }
+    /// Parses the set operator between two selects of a compound one, if any
+    fn parse_set_operator(&mut self) -> Option<SetOperator> {
+        let op = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Union) => SetOperator::Union,
+            SqlToken::Keyword(SqlKeyword::Intersect) => SetOperator::Intersect,
+            SqlToken::Keyword(SqlKeyword::Except) => SetOperator::Except,
+            _ => return None,
+        };
+        self.advance();
+        if op == SetOperator::Union && *self.current() == SqlToken::Keyword(SqlKeyword::All) {
+            self.advance();
+            return Some(SetOperator::UnionAll);
+        }
+        Some(op)
+    }
+
+    /// Parses a select, followed by any number of 'UNION [ALL]', 'INTERSECT'
+    /// or 'EXCEPT' selects
+    fn parse_compound_select(&mut self) -> Result<CompoundSelect, String> {
+        let first = self.parse_select()?;
+        let mut rest = vec![];
+        while let Some(op) = self.parse_set_operator() {
+            rest.push((op, self.parse_select()?));
+        }
+        Ok(CompoundSelect {first, rest})
+    }
+
+    fn parse_select_statement(&mut self) -> Result<Statement, String> {
+        let compound = self.parse_compound_select()?;
+        if compound.rest.is_empty() {
+            Ok(Statement::Select(compound.first))
+        } else {
+            Ok(Statement::Compound(compound))
+        }
+    }
+
+    /// Parses 'WITH [RECURSIVE] name [(col, ...)] AS (query), ... select'
+    fn parse_with(&mut self) -> Result<WithStatement, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::With))?;
+        let recursive = *self.current() == SqlToken::Keyword(SqlKeyword::Recursive);
+        if recursive {
+            self.advance();
+        }
+        let ctes = self.parse_comma_separated(|p| {
+            let name = p.parse_identifier()?;
+            let columns = if *p.current() == SqlToken::LeftParen {
+                p.advance();
+                let columns = p.parse_comma_separated(|p| p.parse_identifier())?;
+                p.expect(SqlToken::RightParen)?;
+                Some(columns)
+            } else {
+                None
+            };
+            p.expect(SqlToken::Keyword(SqlKeyword::As))?;
+            p.expect(SqlToken::LeftParen)?;
+            let query = p.parse_compound_select()?;
+            p.expect(SqlToken::RightParen)?;
+            Ok(Cte {name, columns, query})
+        })?;
+        let body = self.parse_compound_select()?;
+        Ok(WithStatement {recursive, ctes, body})
+    }
+
+    /// Parses a statment reading rows: a select, compound or not, or a WITH
+    fn parse_query(&mut self) -> Result<Statement, String> {
+        match self.current() {
+            SqlToken::Keyword(SqlKeyword::Select) => self.parse_select_statement(),
+            SqlToken::Keyword(SqlKeyword::With) => Ok(Statement::With(self.parse_with()?)),
+            token => Err(format!("Expected SELECT or WITH, found {:?}", token)),
+        }
+    }
+
+    /// Parses an optional 'MATERIALIZED' followed by 'VIEW'
+    fn parse_view_keyword(&mut self) -> Result<bool, String> {
+        let materialized = *self.current() == SqlToken::Keyword(SqlKeyword::Materialized);
+        if materialized {
+            self.advance();
+        }
+        self.expect(SqlToken::Keyword(SqlKeyword::View))?;
+        Ok(materialized)
+    }
+
+    /// Parses 'CREATE [MATERIALIZED] VIEW' or 'CREATE TRIGGER'
+    fn parse_create(&mut self) -> Result<Statement, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Create))?;
+        match self.current() {
+            SqlToken::Keyword(SqlKeyword::Trigger) => Ok(Statement::CreateTrigger(self.parse_create_trigger()?)),
+            _ => Ok(Statement::CreateView(self.parse_create_view()?)),
+        }
+    }
+
+    fn parse_create_view(&mut self) -> Result<CreateViewStatement, String> {
+        let materialized = self.parse_view_keyword()?;
+        let name = self.parse_identifier()?;
+        self.expect(SqlToken::Keyword(SqlKeyword::As))?;
+        let query = self.parse_query()?;
+        Ok(CreateViewStatement {name, materialized, query: Box::new(query)})
+    }
+
+    /// Parses the rest of 'CREATE TRIGGER name BEFORE|AFTER INSERT ON table FOR EACH ROW body',
+    /// the body being an insert or a query
+    fn parse_create_trigger(&mut self) -> Result<CreateTriggerStatement, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Trigger))?;
+        let name = self.parse_identifier()?;
+        let timing = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Before) => TriggerTiming::Before,
+            SqlToken::Keyword(SqlKeyword::After) => TriggerTiming::After,
+            token => return Err(format!("Expected BEFORE or AFTER, found {:?}", token)),
+        };
+        self.advance();
+        self.expect(SqlToken::Keyword(SqlKeyword::Insert))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::On))?;
+        let table = self.parse_identifier()?;
+        self.expect(SqlToken::Keyword(SqlKeyword::For))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::Each))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::Row))?;
+        let body = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Insert) => Statement::Insert(self.parse_insert()?),
+            _ => self.parse_query()?,
+        };
+        Ok(CreateTriggerStatement {name, timing, table, body: Box::new(body)})
+    }
+
+    /// Parses 'DROP [MATERIALIZED] VIEW name', both kinds being dropped alike,
+    /// or 'DROP TRIGGER name'
+    fn parse_drop(&mut self) -> Result<Statement, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Drop))?;
+        if *self.current() == SqlToken::Keyword(SqlKeyword::Trigger) {
+            self.advance();
+            return Ok(Statement::DropTrigger(self.parse_identifier()?));
+        }
+        self.parse_view_keyword()?;
+        Ok(Statement::DropView(self.parse_identifier()?))
+    }
+
+    fn parse_refresh_view(&mut self) -> Result<String, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::Refresh))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::Materialized))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::View))?;
+        self.parse_identifier()
+    }
+
     pub fn parse_insert(&mut self) -> Result<InsertStatement, String> {
         self.expect(SqlToken::Keyword(SqlKeyword::Insert))?;
         self.expect(SqlToken::Keyword(SqlKeyword::Into))?;
@@ -157,35 +619,183 @@ impl Parser {
             }
             _ => return Err("Expected table name".into()),
         };
-        self.expect(SqlToken::LeftParen)?;
-        let columns = self.parse_comma_separated(|p| p.parse_identifier())?;
-        self.expect(SqlToken::RightParen)?;
+        let columns = if *self.current() == SqlToken::LeftParen {
+            self.advance();
+            let columns = self.parse_comma_separated(|p| p.parse_identifier())?;
+            self.expect(SqlToken::RightParen)?;
+            Some(columns)
+        } else {
+            None
+        };
 
-        self.expect(SqlToken::Keyword(SqlKeyword::Values))?;
+        let source = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Select) => InsertSource::Select(Box::new(self.parse_select()?)),
+            _ => {
+                self.expect(SqlToken::Keyword(SqlKeyword::Values))?;
+                InsertSource::Values(self.parse_comma_separated(|p| p.parse_value_tuple())?)
+            }
+        };
+        let on_conflict = if *self.current() == SqlToken::Keyword(SqlKeyword::On) {
+            Some(self.parse_on_conflict()?)
+        } else {
+            None
+        };
+        let returning = if *self.current() == SqlToken::Keyword(SqlKeyword::Returning) {
+            self.advance();
+            if *self.current() == SqlToken::Star {
+                self.advance();
+                Some(Returning::All)
+            } else {
+                Some(Returning::Columns(self.parse_comma_separated(|p| p.parse_identifier())?))
+            }
+        } else {
+            None
+        };
+        Ok(InsertStatement {columns, table, source, on_conflict, returning})
+    }
+    fn parse_on_conflict(&mut self) -> Result<OnConflict, String> {
+        self.expect(SqlToken::Keyword(SqlKeyword::On))?;
+        self.expect(SqlToken::Keyword(SqlKeyword::Conflict))?;
+        let target = if *self.current() == SqlToken::LeftParen {
+            self.advance();
+            let t = self.parse_identifier()?;
+            self.expect(SqlToken::RightParen)?;
+            Some(t)
+        } else {
+            None
+        };
+        self.expect(SqlToken::Keyword(SqlKeyword::Do))?;
+        let action = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Nothing) => {
+                self.advance();
+                ConflictAction::Nothing
+            }
+            SqlToken::Keyword(SqlKeyword::Update) => {
+                self.advance();
+                self.expect(SqlToken::Keyword(SqlKeyword::Set))?;
+                ConflictAction::Update(self.parse_comma_separated(|p| {
+                    let col = p.parse_identifier()?;
+                    p.expect(SqlToken::Operator(Operator::Equal))?;
+                    Ok((col, p.parse_expr()?))
+                })?)
+            }
+            token => return Err(format!("Expected NOTHING or UPDATE, found {:?}", token)),
+        };
+        Ok(OnConflict {target, action})
+    }
+    fn parse_value_tuple(&mut self) -> Result<Vec<Expr>, String> {
         self.expect(SqlToken::LeftParen)?;
+        let values = self.parse_comma_separated(|p| p.parse_expr())?;
+        self.expect(SqlToken::RightParen)?;
+        Ok(values)
+    }
+    /// Parses the statment starting at the current token
+    fn parse_statment(&mut self) -> Result<ASTRootWrapper, String> {
+        self.last_parameter = 0;
+        let statment = match self.current() {
+            SqlToken::Keyword(SqlKeyword::Select) => self.parse_select_statement()?,
+            SqlToken::Keyword(SqlKeyword::Insert) => Statement::Insert(self.parse_insert()?),
+            SqlToken::Keyword(SqlKeyword::With) => Statement::With(self.parse_with()?),
+            SqlToken::Keyword(SqlKeyword::Create) => self.parse_create()?,
+            SqlToken::Keyword(SqlKeyword::Drop) => self.parse_drop()?,
+            SqlToken::Keyword(SqlKeyword::Refresh) => Statement::RefreshView(self.parse_refresh_view()?),
+            SqlToken::EOF => return Err("error, expected input".to_string()),
+            _ => return Err("error, expected keyword at the first position".to_string()),
+        };
+        Ok(ASTRootWrapper{first_node: ASTNode::Statment(statment)})
+    }
+    /// Parses a single statment, optionally followed by semicolons
+    ///
+    /// # Errors
+    ///
+    /// The parse error, or an error if anything but ';' follows the statment
+    pub fn generate_ast(&mut self) -> Result<ASTRootWrapper, String> {
+        let ast_root = self.parse_statment()?;
+        self.expect_statment_end()?;
+        while *self.current() == SqlToken::Semicolon {
+            self.advance();
+        }
+        match self.current() {
+            SqlToken::EOF => Ok(ast_root),
+            token => Err(format!("Expected a single statment, found {:?} after it", token)),
+        }
+    }
 
-        let mut values = Vec::new();
-        while *self.current() != SqlToken::RightParen {
-            if *self.current() == SqlToken::RightParen {break}
-            values.push(self.parse_expr()?);
+    /// Checks the statment just parsed is followed by a ';' or the end of
+    /// the input
+    fn expect_statment_end(&self) -> Result<(), String> {
+        match self.current() {
+            SqlToken::Semicolon | SqlToken::EOF => Ok(()),
+            token => Err(format!("Expected ';' or the end of the input, found {:?}", token)),
+        }
+    }
 
-            if *self.current() == SqlToken::Comma {
+    /// Parses a script, statments separated by semicolons, the empty ones
+    /// being skipped
+    ///
+    /// # Errors
+    ///
+    /// The index of the statment that failed to parse, counted from 0, along
+    /// with the error
+    pub fn generate_script(&mut self) -> Result<Vec<ASTRootWrapper>, (usize, String)> {
+        let mut statments = vec![];
+        loop {
+            while *self.current() == SqlToken::Semicolon {
                 self.advance();
             }
+            if *self.current() == SqlToken::EOF {
+                return Ok(statments);
+            }
+            let idx = statments.len();
+            statments.push(self.parse_statment().map_err(|e| (idx, e))?);
+            self.expect_statment_end().map_err(|e| (idx, e))?;
         }
-        self.expect(SqlToken::RightParen)?;
-        Ok(InsertStatement {columns: Some(columns), table, values})
     }
-    pub fn generate_ast(&mut self) -> Result<ASTRootWrapper, String> {
-        let base_node = match self.tokens.get(0) {
-            Some(s) => match s {
-                SqlToken::Keyword(SqlKeyword::Select) => {ASTRootWrapper{first_node: ASTNode::Statment(Statement::Select(self.parse_select()?))}},
-                SqlToken::Keyword(SqlKeyword::Insert) => {ASTRootWrapper{first_node: ASTNode::Statment(Statement::Insert(self.parse_insert()?))}},
-                _ => return Err("error, expected keyword at the first position".to_string()),
-            },
-            _ => return Err("error, expected input".to_string()),
+}
 
-        };
-        Ok(base_node)
+/// Lexes and parses a single statment
+pub fn parse_sql(sql_s: &str) -> Result<ASTRootWrapper, String> {
+    let mut lx = Lexer {
+        input: sql_s.chars().peekable(),
+    };
+    Parser::new(lx.lex()?).generate_ast()
+}
+
+/// Lexes and parses a script of statments separated by semicolons
+///
+/// # Errors
+///
+/// ScriptError, with the index of the failing statment if it did not parse
+pub fn parse_script(sql_s: &str) -> Result<Vec<ASTRootWrapper>, ScriptError> {
+    let mut lx = Lexer {
+        input: sql_s.chars().peekable(),
+    };
+    let tokens = lx.lex().map_err(ScriptError::Lexing)?;
+    Parser::new(tokens).generate_script().map_err(|(idx, e)| ScriptError::Statment(idx, Box::new(ParseError(e))))
+}
+
+/// Lexes and parses a column of a table header after its name
+pub fn parse_column_sql(column_s: &str) -> Result<ColumnDef, String> {
+    let mut lx = Lexer {
+        input: column_s.chars().peekable(),
+    };
+    let mut parser = Parser::new(lx.lex()?);
+    let column = parser.parse_column_def()?;
+    match parser.current() {
+        SqlToken::EOF => Ok(column),
+        token => Err(format!("Unexpected {:?} after the column", token)),
+    }
+}
+
+/// Lexes and parses a lone expression, as those of the generated columns
+pub fn parse_expr_sql(expr_s: &str) -> Result<Expr, String> {
+    let mut lx = Lexer {
+        input: expr_s.chars().peekable(),
+    };
+    let mut parser = Parser::new(lx.lex()?);
+    let expr = parser.parse_expr()?;
+    match parser.current() {
+        SqlToken::EOF => Ok(expr),
+        token => Err(format!("Unexpected {:?} after the expression", token)),
     }
 }
@@ -0,0 +1,128 @@
+/*  ## Pattern matching ##
+ *  The wildcard matchers behind LIKE, ILIKE and GLOB
+ *
+ *  LIKE  -- '%' any run of chars, '_' exactly one char, an optional escape char
+ *           makes the next char literal
+ *  GLOB  -- '*' any run, '?' one char, '[abc]' / '[a-z]' / '[^...]' char classes,
+ *           always case sensitive
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+enum PatternToken {
+    Char(char),
+    AnyOne,
+    AnyMany,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl PatternToken {
+    fn matches_char(&self, c: char, case_insensitive: bool) -> bool {
+        match self {
+            PatternToken::Char(p) => {
+                if case_insensitive {
+                    p.to_lowercase().eq(c.to_lowercase())
+                } else {
+                    *p == c
+                }
+            }
+            PatternToken::AnyOne => true,
+            PatternToken::AnyMany => false,
+            PatternToken::Class { negated, ranges } => {
+                ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi) != *negated
+            }
+        }
+    }
+}
+
+fn compile_like(pattern: &str, escape: Option<char>) -> Vec<PatternToken> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if Some(c) == escape {
+            // a trailing escape char stands for itself
+            tokens.push(PatternToken::Char(chars.next().unwrap_or(c)));
+            continue;
+        }
+        tokens.push(match c {
+            '%' => PatternToken::AnyMany,
+            '_' => PatternToken::AnyOne,
+            c => PatternToken::Char(c),
+        });
+    }
+    tokens
+}
+
+fn compile_glob(pattern: &str) -> Vec<PatternToken> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => tokens.push(PatternToken::AnyMany),
+            '?' => tokens.push(PatternToken::AnyOne),
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = vec![];
+                let mut closed = false;
+                // a ']' right after the opening bracket is a member, not the end
+                let mut first = true;
+                while let Some(lo) = chars.next() {
+                    if lo == ']' && !first {
+                        closed = true;
+                        break;
+                    }
+                    first = false;
+                    let mut hi = lo;
+                    if chars.peek() == Some(&'-') {
+                        chars.next();
+                        match chars.peek() {
+                            Some(']') | None => ranges.push(('-', '-')),
+                            Some(_) => hi = chars.next().unwrap_or(lo),
+                        }
+                    }
+                    ranges.push((lo, hi));
+                }
+                if !closed {
+                    // an unterminated class can never match
+                    return vec![PatternToken::Class { negated: false, ranges: vec![] }];
+                }
+                tokens.push(PatternToken::Class { negated, ranges });
+            }
+            c => tokens.push(PatternToken::Char(c)),
+        }
+    }
+    tokens
+}
+
+/// Wildcard matching with backtracking to the last 'AnyMany' seen
+fn match_tokens(tokens: &[PatternToken], text: &str, case_insensitive: bool) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < tokens.len() && tokens[p] == PatternToken::AnyMany {
+            backtrack = Some((p + 1, t));
+            p += 1;
+        } else if p < tokens.len() && tokens[p].matches_char(text[t], case_insensitive) {
+            p += 1;
+            t += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    tokens[p..].iter().all(|tkn| *tkn == PatternToken::AnyMany)
+}
+
+pub fn like_match(text: &str, pattern: &str, escape: Option<char>, case_insensitive: bool) -> bool {
+    match_tokens(&compile_like(pattern, escape), text, case_insensitive)
+}
+
+pub fn glob_match(text: &str, pattern: &str) -> bool {
+    match_tokens(&compile_glob(pattern), text, false)
+}
@@ -0,0 +1,259 @@
+/*  ## Prepared statments ##
+ *  A statment parsed once and run any number of times, the values of its '?',
+ *  '$n' and ':name' placeholders being given on each run. The values go into
+ *  the syntax tree as literals, they are never pasted into the sql itself.
+ */
+use std::convert::Infallible;
+use crate::ast::{ASTNode, ASTRootWrapper, ConflictAction, Cte, Expr, InsertSource, Literal, OnConflict, Parameter, SelectStatement, Statement};
+use crate::database::{DBColumn, DBField, DataTypes, db::DB};
+use crate::engine::{Engine, QueryResult, expr_type, unqualify_select};
+use crate::parser::parse_sql;
+use crate::sql::{Operator, errors::EngineError};
+
+#[derive(Debug)]
+pub struct PreparedStatement {
+    ast_root: ASTRootWrapper,
+    /// each parameter once, in the order they first appear
+    parameters: Vec<Parameter>,
+}
+
+impl PreparedStatement {
+    /// Parses the statment once for all of its runs
+    ///
+    /// # Errors
+    ///
+    /// The parser error if the sql is not a valid statment
+    pub fn new(sql_s: &str) -> Result<Self, String> {
+        let ast_root = parse_sql(sql_s)?;
+        let ASTNode::Statment(statment) = &ast_root.first_node;
+        let mut parameters: Vec<Parameter> = vec![];
+        let Ok(()) = statment.clone().try_for_each_expr_mut(&mut |expr| {
+            if let Expr::Parameter(p) = expr && !parameters.contains(p) {
+                parameters.push(p.clone());
+            }
+            Ok::<(), Infallible>(())
+        });
+        Ok(PreparedStatement {ast_root, parameters})
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// Runs the statment, 'values[n - 1]' standing for '$n' as well as for
+    /// the n-th '?'
+    ///
+    /// # Errors
+    ///
+    /// EngineError if there is not exactly one value per number, a value does
+    /// not have the type of the column it is stored in or compared to, or the
+    /// statment holds ':name' parameters. Otherwise same as 'Engine::run_on_db'.
+    pub fn execute(&self, db: &mut DB, values: &[DBField]) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let expected = self.parameters.iter()
+            .filter_map(|p| if let Parameter::Index(n) = p {Some(*n)} else {None})
+            .max()
+            .unwrap_or(0);
+        if values.len() != expected {
+            return Err(Box::new(EngineError::ParameterCount(expected, values.len())));
+        }
+        self.run(db, |p| match p {
+            Parameter::Index(n) => values.get(n - 1),
+            Parameter::Name(_) => None,
+        })
+    }
+
+    /// Runs the statment, binding the ':name' parameters by name
+    ///
+    /// # Errors
+    ///
+    /// Same as 'execute', the values being matched by name rather than number
+    pub fn execute_named(&self, db: &mut DB, values: &[(&str, DBField)]) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let expected = self.parameters.iter().filter(|p| matches!(p, Parameter::Name(_))).count();
+        if values.len() != expected {
+            return Err(Box::new(EngineError::ParameterCount(expected, values.len())));
+        }
+        self.run(db, |p| match p {
+            Parameter::Name(name) => values.iter().find(|(n, _)| n == name).map(|(_, f)| f),
+            Parameter::Index(_) => None,
+        })
+    }
+
+    fn run<'v>(
+        &self,
+        db: &mut DB,
+        lookup: impl Fn(&Parameter) -> Option<&'v DBField>,
+    ) -> Result<QueryResult, Box<dyn std::error::Error>> {
+        let ASTNode::Statment(statment) = &self.ast_root.first_node;
+        let mut types = vec![];
+        statment_types(statment, &mut Scope {db, ctes: vec![], views: vec![]}, &mut types);
+        let mut bound = statment.clone();
+        bound.try_for_each_expr_mut(&mut |expr| {
+            let Expr::Parameter(p) = expr else { return Ok(()) };
+            let Some(value) = lookup(p) else {
+                return Err(EngineError::UnboundParameter(p.to_string()));
+            };
+            if let Some((_, dt)) = types.iter().find(|(typed, dt)| typed == p && !dt.accepts(value)) {
+                return Err(EngineError::ParameterType(p.to_string(), value.clone(), dt.clone()));
+            }
+            *expr = Expr::Literal(Literal::from(value));
+            Ok(())
+        })?;
+        Engine::new(ASTRootWrapper {first_node: ASTNode::Statment(bound)}).run_on_db(db)
+    }
+}
+
+/// What the names of a statment resolve to when typing its parameters: the
+/// tables and views of the database, and the CTEs seen so far with their
+/// columns typed from their queries
+struct Scope<'a> {
+    db: &'a DB,
+    ctes: Vec<(String, Vec<DBColumn>)>,
+    /// the views being typed, a view reading itself again is left untyped
+    views: Vec<String>,
+}
+
+impl Scope<'_> {
+    /// The columns of the CTE, table or view 'name', those of a CTE or view as
+    /// its query types them. Empty if there is no such one.
+    fn header(&self, name: &str) -> Vec<DBColumn> {
+        if let Some((_, header)) = self.ctes.iter().find(|(cte, _)| cte == name) {
+            return header.clone();
+        }
+        if let Some(tb) = self.db.get_table(name) {
+            return tb.header().to_vec();
+        }
+        match self.db.get_view(name) {
+            Some(view) if !self.views.iter().any(|v| v == name) => {
+                // the query of the view sees none of the CTEs of the statment
+                let views = self.views.iter().cloned().chain([name.to_string()]).collect();
+                let scope = Scope {db: self.db, ctes: vec![], views};
+                scope.query_header(&view.query)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The columns a query returns, typed from its first select, empty for
+    /// those of no known type
+    fn query_header(&self, statment: &Statement) -> Vec<DBColumn> {
+        match statment {
+            Statement::Select(s) => self.select_header(s),
+            Statement::Compound(c) => self.select_header(&c.first),
+            Statement::With(w) => {
+                let mut scope = Scope {db: self.db, ctes: self.ctes.clone(), views: self.views.clone()};
+                w.ctes.iter().for_each(|cte| scope.add_cte(cte));
+                scope.select_header(&w.body.first)
+            }
+            _ => vec![],
+        }
+    }
+
+    fn select_header(&self, s: &SelectStatement) -> Vec<DBColumn> {
+        let names: Vec<String> = s.columns.iter().map(|c| c.output_name()).collect();
+        self.typed_columns(s, &names)
+    }
+
+    /// The columns of the select named 'names', in order, leaving out those
+    /// of no known type
+    fn typed_columns(&self, s: &SelectStatement, names: &[String]) -> Vec<DBColumn> {
+        let header = self.header(&s.table);
+        unqualify_select(s).columns.iter().zip(names)
+            .filter_map(|(c, name)| Some(DBColumn::new(name, expr_type(&c.expr, &header, self.db.functions())?)))
+            .collect()
+    }
+
+    /// Brings the CTE in scope, typed as its query, named by its column list
+    /// if it has one
+    fn add_cte(&mut self, cte: &Cte) {
+        let header = match &cte.columns {
+            Some(columns) => self.typed_columns(&cte.query.first, columns),
+            None => self.select_header(&cte.query.first),
+        };
+        self.ctes.push((cte.name.clone(), header));
+    }
+}
+
+/// Gathers the types of the parameters stored in, or compared to, a column of
+/// a table, a view or a CTE. The other parameters may take any value.
+fn statment_types(statment: &Statement, scope: &mut Scope, out: &mut Vec<(Parameter, DataTypes)>) {
+    match statment {
+        Statement::Insert(i) => {
+            let Some(tb) = scope.db.get_table(&i.table) else { return };
+            let header = tb.header();
+            let targets: Vec<Option<&DBColumn>> = match &i.columns {
+                Some(cols) => cols.iter().map(|c| header.iter().find(|col| col.name == *c)).collect(),
+                None => header.iter().filter(|col| col.generated().is_none()).map(Some).collect(),
+            };
+            match &i.source {
+                InsertSource::Values(tuples) => {
+                    for (expr, col) in tuples.iter().flat_map(|t| t.iter().zip(&targets)) {
+                        if let (Expr::Parameter(p), Some(col)) = (expr, col) {
+                            out.push((p.clone(), col.data_type().clone()));
+                        }
+                        expr_types(expr, &[], scope, out);
+                    }
+                }
+                InsertSource::Select(s) => select_types(s, scope, out),
+            }
+            if let Some(OnConflict { action: ConflictAction::Update(assignments), .. }) = &i.on_conflict {
+                for (col_nm, expr) in assignments {
+                    if let (Expr::Parameter(p), Some(col)) = (expr, header.iter().find(|col| col.name == *col_nm)) {
+                        out.push((p.clone(), col.data_type().clone()));
+                    }
+                    expr_types(expr, header, scope, out);
+                }
+            }
+        }
+        Statement::Select(s) => select_types(s, scope, out),
+        Statement::Compound(c) => c.parts().for_each(|s| select_types(s, scope, out)),
+        Statement::With(w) => {
+            // each CTE only sees those before it
+            for cte in &w.ctes {
+                cte.query.parts().for_each(|s| select_types(s, scope, out));
+                scope.add_cte(cte);
+            }
+            w.body.parts().for_each(|s| select_types(s, scope, out));
+        }
+        Statement::CreateView(v) => statment_types(&v.query, scope, out),
+        Statement::CreateTrigger(t) => statment_types(&t.body, scope, out),
+        Statement::DropView(_) | Statement::RefreshView(_) | Statement::DropTrigger(_) => {}
+    }
+}
+
+fn select_types(s: &SelectStatement, scope: &Scope, out: &mut Vec<(Parameter, DataTypes)>) {
+    let header = scope.header(&s.table);
+    for expr in s.columns.iter().map(|c| &c.expr).chain(s.where_clause.iter().flatten()) {
+        expr_types(expr, &header, scope, out);
+    }
+}
+
+/// Types the parameters compared to a column of 'header', through a comparison
+/// operator, IN or BETWEEN, going on into the subqueries with their own tables
+fn expr_types(expr: &Expr, header: &[DBColumn], scope: &Scope, out: &mut Vec<(Parameter, DataTypes)>) {
+    let mut pairs: Vec<(&Expr, &Expr)> = vec![];
+    match expr {
+        Expr::Binary {
+            left,
+            op: Operator::Equal | Operator::NotEqual | Operator::Greater | Operator::Smaller
+                | Operator::GreaterEqual | Operator::SmallerEqual,
+            right,
+        } => {
+            pairs.push((left, right));
+            pairs.push((right, left));
+        }
+        Expr::InList { expr, list } => list.iter().for_each(|e| pairs.push((expr, e))),
+        Expr::Between { expr, low, high } => {
+            pairs.push((expr, low));
+            pairs.push((expr, high));
+        }
+        Expr::InSubquery { subquery, .. } | Expr::Exists(subquery) => select_types(subquery, scope, out),
+        _ => {}
+    }
+    for (column, operand) in pairs {
+        let (Expr::Identifier(name) | Expr::QualifiedIdentifier { name, .. }, Expr::Parameter(p)) = (column, operand) else { continue };
+        if let Some(col) = header.iter().find(|col| col.name == *name) {
+            out.push((p.clone(), col.data_type().clone()));
+        }
+    }
+    expr.children().into_iter().for_each(|e| expr_types(e, header, scope, out));
+}
@@ -0,0 +1,68 @@
+/*  ## Windows ##
+ *  Splitting and ordering the scanned rows for the window functions
+ *
+ *  The rows are referred to by their index in the scan, a partition being the
+ *  indices sharing the same PARTITION BY values, in the order of the scan until
+ *  sorted on the ORDER BY values. Peers are the rows of a partition with equal
+ *  ORDER BY values, without an ORDER BY the whole partition is one peer group.
+ */
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::database::DBField;
+
+/// Orders two fields: NULL first, then INTs numerically, then TEXTs byte-wise
+pub fn order_fields(a: &DBField, b: &DBField) -> Ordering {
+    match (a, b) {
+        (DBField::Int(x), DBField::Int(y)) => x.cmp(y),
+        (DBField::Text(x), DBField::Text(y)) => x.cmp(y),
+        _ => rank_of(a).cmp(&rank_of(b)),
+    }
+}
+
+fn rank_of(field: &DBField) -> u8 {
+    match field {
+        DBField::Null => 0,
+        DBField::Int(_) => 1,
+        DBField::Text(_) => 2,
+    }
+}
+
+/// Groups the row indices by their partition key, the partitions coming in the
+/// order their first row was scanned
+pub fn partitions(keys: &[Vec<DBField>]) -> Vec<Vec<usize>> {
+    let mut positions: HashMap<&[DBField], usize> = HashMap::new();
+    let mut out: Vec<Vec<usize>> = vec![];
+    for (idx, key) in keys.iter().enumerate() {
+        let pos = *positions.entry(key.as_slice()).or_insert_with(|| {
+            out.push(vec![]);
+            out.len() - 1
+        });
+        out[pos].push(idx);
+    }
+    out
+}
+
+/// Sorts the row indices of a partition on their order keys, stable so peers
+/// stay in the order of the scan
+pub fn sort_partition(partition: &mut [usize], keys: &[Vec<DBField>], descending: &[bool]) {
+    partition.sort_by(|a, b| {
+        keys[*a].iter().zip(&keys[*b]).zip(descending)
+            .map(|((x, y), desc)| if *desc {order_fields(y, x)} else {order_fields(x, y)})
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// The positions in the sorted partition of each group of peers
+pub fn peer_groups(partition: &[usize], keys: &[Vec<DBField>]) -> Vec<Range<usize>> {
+    let mut out = vec![];
+    let mut start = 0;
+    for pos in 1..=partition.len() {
+        if pos == partition.len() || keys[partition[pos]] != keys[partition[start]] {
+            out.push(start..pos);
+            start = pos;
+        }
+    }
+    out
+}
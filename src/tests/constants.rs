@@ -1,5 +1,5 @@
 #![allow(unused)]
-pub const TEST_TABLE_CONTENTS: &'static str = "
+pub const TEST_TABLE_CONTENTS: &str = "
 id: INT, name: TEXT
 0, Bob
 1, Alice
@@ -7,6 +7,6 @@ id: INT, name: TEXT
 3, Jane
 4, Tod
 5, Ann\n";   
-pub const TEST_DB_PATH: &'static str = "test_db";
-pub const TEST_TABLE_PATH: &'static str = "test_db/test_table.txt";
-pub const TEST_TABLE_NAME: &'static str = "test_table";
+pub const TEST_DB_PATH: &str = "test_db";
+pub const TEST_TABLE_PATH: &str = "test_db/test_table.txt";
+pub const TEST_TABLE_NAME: &str = "test_table";
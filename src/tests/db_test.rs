@@ -1,10 +1,13 @@
-use std::fs::{OpenOptions, create_dir, exists};
+use std::fs::{File, OpenOptions, create_dir, exists, read_to_string, remove_file};
 use std::path::Path;
 use std::io::Write;
-use crate::{Expr, Operator, SelectStatement, Table, engine};
-use crate::database::{DBField, db};
+use crate::{Expr, Operator, PreparedStatement, QueryResult, SelectItem, SelectStatement, Table, engine, sql};
+use crate::database::{DBField, DataTypes, db};
 use super::constants::*;
 use crate::Literal;
+use crate::functions::FunctionRegistry;
+use crate::db_errors::DBError;
+use crate::sql_errors::EngineError;
 
 fn setup_mock_db() {
     let db_dir_path = Path::new(TEST_DB_PATH);
@@ -13,7 +16,7 @@ fn setup_mock_db() {
     }
     let table_file_path = Path::new(TEST_TABLE_PATH);
     let mut table_f = OpenOptions::new().create(true).write(true).truncate(true).open(table_file_path).unwrap(); 
-    table_f.write(TEST_TABLE_CONTENTS.as_bytes()).unwrap();
+    table_f.write_all(TEST_TABLE_CONTENTS.as_bytes()).unwrap();
 }
 
 /// checking if writing a string yields the correct entries in the 'Table' struct
@@ -27,23 +30,63 @@ fn test_db() {
     
     test_db_content_integrity(&mut db);
     test_db_insert_to_all(&mut db);
+    test_db_insert_many(&mut db);
+    test_db_insert_without_columns(&mut db);
+    test_db_upsert(&mut db);
+    test_db_insert_returning(&mut db);
+    test_db_arithmetic(&mut db);
+    test_db_comparisons(&mut db);
+    test_db_pattern_matching(&mut db);
+    test_db_in_between_subqueries(&mut db);
+    test_db_functions(&mut db);
+    test_db_user_functions(&mut db);
+    test_db_case(&mut db);
+    test_db_aliases(&mut db);
+    test_db_distinct_compound(&mut db);
+    test_db_ctes(&mut db);
+    test_db_windows(&mut db);
+    test_db_file_format(&mut db);
+    test_db_views(&mut db);
+    test_db_materialized_views(&mut db);
+    test_db_triggers(&mut db);
+    test_db_generated_columns(&mut db);
+    test_db_scripts(&mut db);
+    test_db_quoted_identifiers(&mut db);
+    test_db_prepared_statements(&mut db);
+    test_db_execute_and_query(&mut db);
+    test_db_result_metadata(&mut db);
+    test_db_query_iter(&mut db);
 }
 
 fn test_db_content_integrity(db: &mut db::DB) {
     let table = db.get_mut_table(TEST_TABLE_NAME).unwrap();
     let table_vec = table.select_all_cols().unwrap();
 
-    let mut test_vec: Vec<Vec<DBField>> = vec![vec![]];
-    test_vec.push(vec![DBField::Int(0), DBField::Text("Bob".to_string())]);
-    test_vec.push(vec![DBField::Int(1), DBField::Text("Alice".to_string())]);
-    test_vec.push(vec![DBField::Int(2), DBField::Text("Rob".to_string())]);
-    test_vec.push(vec![DBField::Int(3), DBField::Text("Jane".to_string())]);
-    test_vec.push(vec![DBField::Int(4), DBField::Text("Tod".to_string())]);
-    test_vec.push(vec![DBField::Int(5), DBField::Text("Ann".to_string())]);
+    let test_vec: Vec<Vec<DBField>> = vec![
+        vec![DBField::Int(0), DBField::Text("Bob".to_string())],
+        vec![DBField::Int(1), DBField::Text("Alice".to_string())],
+        vec![DBField::Int(2), DBField::Text("Rob".to_string())],
+        vec![DBField::Int(3), DBField::Text("Jane".to_string())],
+        vec![DBField::Int(4), DBField::Text("Tod".to_string())],
+        vec![DBField::Int(5), DBField::Text("Ann".to_string())],
+    ];
     assert_eq!(test_vec, table_vec);
     println!("#Test# table integrity correct");
 }
 
+fn test_db_file_format(db: &mut db::DB) {
+    // inserting no row still writes the table out
+    run_sql(db, "INSERT INTO test_table (id, name) SELECT id, name FROM test_table WHERE id < -100");
+    // the header sits on a line of its own, followed by one line per row
+    let contents = read_to_string(format!("{}.txt", TEST_TABLE_NAME)).unwrap();
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("id: INT, name: TEXT"));
+    assert_eq!(lines.next(), Some("0, Bob"));
+    assert!(lines.all(|line| line.is_empty() || line.contains(", ")));
+    // and the file reads back in
+    assert!(db::DB::open(".").unwrap().get_table(TEST_TABLE_NAME).is_some());
+}
+
 fn test_db_insert_to_all(db: &mut db::DB) {
     setup_mock_db();
     let table = db.get_mut_table(TEST_TABLE_NAME).unwrap();
@@ -57,14 +100,691 @@ fn test_db_inserted_correctly(table: &mut Table) {
         op: Operator::Equal, 
         right: Box::new(Expr::Literal(Literal::Number(1)))};
 
-    let s_statmen = SelectStatement{columns: vec!["name".to_string()], table: "users".to_string(), where_clause: Option::Some(vec![w_expr.clone()])};
-    let eng = engine::Engine{ast_root: crate::ASTRootWrapper { first_node: crate::ASTNode::Statment(crate::Statement::Select(s_statmen)) }};
-    let res = table.select_where(vec!["id".to_string(), "name".to_string()], &vec![w_expr.clone()], &eng).unwrap();
+    let s_statmen = SelectStatement{distinct: false, columns: vec![SelectItem {expr: Expr::Identifier("name".to_string()), alias: None}], table: "users".to_string(), table_alias: None, where_clause: Option::Some(vec![w_expr.clone()])};
+    let eng = engine::Engine::new(crate::ASTRootWrapper { first_node: crate::ASTNode::Statment(crate::Statement::Select(s_statmen)) });
+    let res = table.select_where(vec!["id".to_string(), "name".to_string()], &[w_expr], &eng).unwrap();
 
-    if res.len() < 2 {
+    if res.is_empty() {
         dbg!(res);
         panic!("wrong amount of rows returned")
     }
-    assert_eq!(res[1], vec![DBField::Int(1), DBField::Text("Alice".to_string())])
+    assert_eq!(res[0], vec![DBField::Int(1), DBField::Text("Alice".to_string())])
+}
+
+
+fn try_sql(db: &mut db::DB, sql_s: &str) -> Result<QueryResult, Box<dyn std::error::Error>> {
+    db.execute(sql_s)
+}
+
+fn run_sql(db: &mut db::DB, sql_s: &str) -> QueryResult {
+    try_sql(db, sql_s).unwrap()
+}
+
+fn test_db_insert_many(db: &mut db::DB) {
+    run_sql(db, "INSERT INTO test_table (name, id) VALUES ('Eve', 6), ('Mallory', 7)");
+    run_sql(db, "INSERT INTO test_table (id, name) SELECT id, name FROM test_table WHERE id > 5");
+    let res = run_sql(db, "SELECT id, name FROM test_table WHERE id > 5").into_rows();
+    assert_eq!(res.len(), 4);
+    assert_eq!(res[0], vec![DBField::Int(6), DBField::Text("Eve".to_string())]);
+    assert_eq!(res[3], vec![DBField::Int(7), DBField::Text("Mallory".to_string())]);
+
+    let table = db.get_mut_table(TEST_TABLE_NAME).unwrap();
+    let rejected = table.insert_many(None, vec![
+        vec![DBField::Int(8), DBField::Text("Trent".to_string())],
+        vec![DBField::Text("9".to_string()), DBField::Text("Peggy".to_string())],
+    ], &FunctionRegistry::default());
+    assert!(rejected.is_err());
+    assert_eq!(table.select_all_cols().unwrap().len(), 11);
+}
+
+fn test_db_insert_without_columns(db: &mut db::DB) {
+    run_sql(db, "INSERT INTO test_table VALUES (8, 'Trent')");
+    let res = run_sql(db, "SELECT name FROM test_table WHERE id = 8").into_rows();
+    assert_eq!(res[0], vec![DBField::Text("Trent".to_string())]);
+
+    let err = try_sql(db, "INSERT INTO test_table VALUES (9)").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InsertArityMismatch(2, 1))));
+    let err = try_sql(db, "INSERT INTO test_table VALUES (9, 'Peggy', 'extra')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InsertArityMismatch(2, 3))));
+}
+
+fn test_db_upsert(db: &mut db::DB) {
+    run_sql(db, "INSERT INTO test_table VALUES (2, 'Robert'), (20, 'Zed') ON CONFLICT (id) DO NOTHING");
+    run_sql(db, "INSERT INTO test_table VALUES (3, 'Janet') ON CONFLICT (id) DO UPDATE SET name = excluded.name");
+    let res = run_sql(db, "SELECT id, name FROM test_table WHERE id = 2 OR id = 3 OR id = 20").into_rows();
+    assert_eq!(res, [
+        vec![DBField::Int(2), DBField::Text("Rob".to_string())],
+        vec![DBField::Int(3), DBField::Text("Janet".to_string())],
+        vec![DBField::Int(20), DBField::Text("Zed".to_string())],
+    ]);
+
+    let err = try_sql(db, "INSERT INTO test_table VALUES (4, 'Todd') ON CONFLICT DO NOTHING").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::NoConflictTarget)));
+}
+
+fn test_db_insert_returning(db: &mut db::DB) {
+    let res = run_sql(db, "INSERT INTO test_table (id) VALUES (30), (31) RETURNING *").into_rows();
+    assert_eq!(res, [
+        vec![DBField::Int(30), DBField::Text("".to_string())],
+        vec![DBField::Int(31), DBField::Text("".to_string())],
+    ]);
+
+    let res = run_sql(db, "INSERT INTO test_table VALUES (31, 'Olivia') ON CONFLICT (id) DO UPDATE SET name = excluded.name RETURNING name, id").into_rows();
+    assert_eq!(res, [vec![DBField::Text("Olivia".to_string()), DBField::Int(31)]]);
+
+    // an unknown RETURNING column fails the insert before anything is stored
+    let err = try_sql(db, "INSERT INTO test_table VALUES (32, 'Pat') RETURNING missing").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::ColumnNotFound(_))));
+    assert!(run_sql(db, "SELECT id FROM test_table WHERE id = 32").into_rows().is_empty());
+}
+
+fn test_db_arithmetic(db: &mut db::DB) {
+    run_sql(db, "INSERT INTO test_table VALUES (-2 * 20, 'Neg' || 'ative')");
+    let res = run_sql(db, "SELECT id * -1 + 2, name || '!' FROM test_table WHERE id + 50 < 20 - (id % 7)").into_rows();
+    assert_eq!(res, [vec![DBField::Int(42), DBField::Text("Negative!".to_string())]]);
+
+    let err = try_sql(db, "SELECT id / 0 FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::DivisionByZero))));
+    let err = try_sql(db, "SELECT name - 1 FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::TypeMismatch(..)))));
+}
+
+fn test_db_comparisons(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id >= 2 AND id <= 5 AND NOT (name < 'Ro' OR name = 'Tod')").into_rows();
+    assert_eq!(res, [vec![DBField::Int(2)]]);
+}
+
+fn test_db_pattern_matching(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT id FROM test_table WHERE name LIKE 'A%' AND name NOT LIKE '_nn'").into_rows();
+    assert_eq!(res, [vec![DBField::Int(1)]]);
+    let res = run_sql(db, "SELECT id FROM test_table WHERE name ILIKE 'ALICE' AND name GLOB '[a-z]*'").into_rows();
+    assert_eq!(res, [vec![DBField::Int(1)]]);
+}
+
+fn test_db_in_between_subqueries(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT name FROM test_table WHERE id IN (0, 2, 4) AND id NOT BETWEEN 3 AND 10").into_rows();
+    assert_eq!(res, [vec![DBField::Text("Bob".to_string())], vec![DBField::Text("Rob".to_string())]]);
+
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id IN (SELECT id + 1 FROM test_table WHERE name = 'Bob') AND EXISTS (SELECT id FROM test_table WHERE id = 0)").into_rows();
+    assert_eq!(res, [vec![DBField::Int(1)], vec![DBField::Int(1)]]);
+
+    let res = run_sql(db, "SELECT id FROM test_table WHERE NOT EXISTS (SELECT id FROM test_table WHERE id = 999)").into_rows();
+    assert_eq!(res.len(), db.get_mut_table(TEST_TABLE_NAME).unwrap().select_all_cols().unwrap().len());
+
+    let err = try_sql(db, "SELECT id FROM test_table WHERE id IN (SELECT id, name FROM test_table)").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::SubqueryColumnCount(2)))));
+}
+
+fn test_db_functions(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT UPPER(name) || CAST(id AS TEXT), NULLIF(id, 0) FROM test_table WHERE LENGTH(name) = 3 AND CAST('4' AS INT) = id").into_rows();
+    assert_eq!(res, [vec![DBField::Text("TOD4".to_string()), DBField::Int(4)]]);
+
+    let err = try_sql(db, "SELECT NO_SUCH_FUNCTION(id) FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::UnknownFunction(_)))));
+    let err = try_sql(db, "INSERT INTO test_table VALUES (NULL, 'Nil')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::MistypedInsertInput(DBField::Null, _))));
+
+    // comparisons with NULL are false, which NOT turns into true
+    let all = run_sql(db, "SELECT id FROM test_table").rows().len();
+    assert!(run_sql(db, "SELECT id FROM test_table WHERE name = NULL OR NULLIF(id, id) > 0").rows().is_empty());
+    assert_eq!(run_sql(db, "SELECT id FROM test_table WHERE NOT (name = NULL)").rows().len(), all);
+    let ones = run_sql(db, "SELECT id FROM test_table WHERE id = 1").rows().len();
+    assert_eq!(run_sql(db, "SELECT id FROM test_table WHERE id NOT IN (1, NULL)").rows().len(), all - ones);
+}
+
+fn test_db_user_functions(db: &mut db::DB) {
+    db.register_scalar_function("initial", DataTypes::TEXT, |args| match args {
+        [DBField::Text(s)] => Ok(DBField::Text(s.chars().take(1).collect())),
+        _ => Err("expected a single TEXT".to_string()),
+    });
+    db.register_aggregate_function(
+        "sum_ids",
+        DataTypes::TEXT,
+        || (0, 0),
+        |(sum, count): &mut (i32, i32), args| match args {
+            [DBField::Int(i)] => {
+                *sum += i;
+                *count += 1;
+                Ok(())
+            }
+            _ => Err("expected a single INT".to_string()),
+        },
+        |(sum, count)| Ok(DBField::Text(format!("{}/{}", sum, count))),
+    );
+
+    let res = run_sql(db, "SELECT id FROM test_table WHERE INITIAL(name) = 'J'").into_rows();
+    assert_eq!(res, [vec![DBField::Int(3)]]);
+
+    let res = run_sql(db, "SELECT sum_ids(id * 2), sum_ids(id) || '!' FROM test_table WHERE id < 3 AND id >= 0").into_rows();
+    assert_eq!(res, [vec![
+        DBField::Text("8/4".to_string()),
+        DBField::Text("4/4!".to_string()),
+    ]]);
+    // with no GROUP BY, a column outside of the aggregates has no single value
+    let err = try_sql(db, "SELECT sum_ids(id), initial(name) FROM test_table WHERE id = 999").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::UngroupedColumn(name)) if name == "name"));
+    let err = try_sql(db, "SELECT initial(*) FROM test_table WHERE id = 999").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::StarArgument(name)) if name == "INITIAL"));
+    let err = try_sql(db, "SELECT id FROM test_table WHERE LENGTH(*) > 1").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::StarArgument(_))));
+
+    // run on a lone table, the engine is handed the functions itself
+    let mut functions = FunctionRegistry::default();
+    functions.register_scalar("twice", DataTypes::INT, |args| match args {
+        [DBField::Int(i)] => Ok(DBField::Int(i * 2)),
+        _ => Err("expected an INT".to_string()),
+    });
+    let sql_s = "SELECT twice(id) FROM test_table WHERE id = 3";
+    let crate::ASTNode::Statment(statment) = sql::parser::parse_sql(sql_s).unwrap().first_node;
+    let engine = engine::Engine::new(sql::parser::parse_sql(sql_s).unwrap()).with_functions(functions);
+    let res = engine.run_on_table(db.get_mut_table("test_table").unwrap(), &statment).unwrap();
+    assert_eq!(res.into_rows(), [vec![DBField::Int(6)]]);
+
+    let err = try_sql(db, "SELECT initial(id) FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::FunctionFailed(..)))));
+    let err = try_sql(db, "SELECT id FROM test_table WHERE sum_ids(id) = 1").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::AggregateMisuse(_)))));
+
+    // the columns are typed from the query alone, registered functions by what they return
+    let res = run_sql(db, "SELECT id + 1, name || '!', CAST(id AS TEXT), LENGTH(name), initial(name), NULL FROM test_table WHERE id = 999");
+    assert_eq!(res.types(), [DataTypes::INT, DataTypes::TEXT, DataTypes::TEXT, DataTypes::INT, DataTypes::TEXT, DataTypes::TEXT]);
+    assert_eq!(run_sql(db, "SELECT sum_ids(id) FROM test_table WHERE id = 999").types(), [DataTypes::TEXT]);
+    db.register_scalar_function("lying", DataTypes::INT, |_| Ok(DBField::Text("not a number".to_string())));
+    let err = try_sql(db, "SELECT lying(id) FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::FunctionFailed(..)))));
+}
+
+fn test_db_case(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT CASE WHEN id < 2 THEN 'low' WHEN id < 4 THEN 'mid' ELSE 'high' END, CASE id WHEN 0 THEN 'zero' END FROM test_table WHERE id BETWEEN 0 AND 4 AND CASE WHEN name LIKE 'a%' THEN id > 100 ELSE ABS(id) = id END").into_rows();
+    let text = |s: &str| DBField::Text(s.to_string());
+    assert_eq!(res, [
+        vec![text("low"), text("zero")],
+        vec![text("low"), DBField::Null],
+        vec![text("mid"), DBField::Null],
+        vec![text("mid"), DBField::Null],
+        vec![text("high"), DBField::Null],
+    ]);
+}
+
+fn test_db_aliases(db: &mut db::DB) {
+    let res = run_sql(db, "SELECT t.id AS ident, UPPER(t.name) shout, test_table.id * 2 FROM test_table AS t WHERE t.id = 3");
+    assert_eq!(res.columns(), ["ident", "shout", "test_table.id * 2"]);
+    assert_eq!(res.column_index("shout"), Some(1));
+    let rows = res.into_rows();
+    assert_eq!(rows, [vec![DBField::Int(3), DBField::Text("JANET".to_string()), DBField::Int(6)]]);
+
+    let res = run_sql(db, "INSERT INTO test_table (id, name) VALUES (20, 'Walter') RETURNING name");
+    assert_eq!(res.columns(), ["name"]);
+
+    let err = try_sql(db, "SELECT other.id FROM test_table AS t").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::ColumnNotFound(_)))));
 }
 
+fn test_db_distinct_compound(db: &mut db::DB) {
+    let int_rows = |res: QueryResult| res.rows().iter().map(|row| match row[..] {
+        [DBField::Int(i)] => i,
+        _ => panic!("expected a single INT"),
+    }).collect::<Vec<_>>();
+    let res = run_sql(db, "SELECT DISTINCT id / 2 FROM test_table WHERE id BETWEEN 0 AND 4");
+    assert_eq!(int_rows(res), [0, 1, 2]);
+
+    let res = run_sql(db, "SELECT id AS n FROM test_table WHERE id < 2 AND id >= 0 UNION ALL SELECT id FROM test_table WHERE id = 1");
+    assert_eq!(res.columns(), ["n"]);
+    assert_eq!(int_rows(res), [0, 1, 1, 1, 1]);
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id < 2 AND id >= 0 UNION SELECT id FROM test_table WHERE id = 2");
+    assert_eq!(int_rows(res), [0, 1, 2]);
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id < 4 AND id >= 0 INTERSECT SELECT id FROM test_table WHERE id > 1");
+    assert_eq!(int_rows(res), [2, 3]);
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id < 4 AND id >= 0 EXCEPT SELECT id FROM test_table WHERE id > 1");
+    assert_eq!(int_rows(res), [0, 1]);
+
+    let err = try_sql(db, "SELECT id, name FROM test_table UNION SELECT id FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::CompoundColumnCount(2, 1))));
+    let err = try_sql(db, "SELECT id FROM test_table UNION SELECT name FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::CompoundTypeMismatch(..))));
+
+    // INTERSECT binds tighter than UNION and EXCEPT
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id = 0 UNION SELECT id FROM test_table WHERE id BETWEEN 1 AND 2 INTERSECT SELECT id FROM test_table WHERE id BETWEEN 2 AND 3");
+    assert_eq!(int_rows(res), [0, 2]);
+    let res = run_sql(db, "SELECT id FROM test_table WHERE id BETWEEN 0 AND 3 EXCEPT SELECT id FROM test_table WHERE id < 2 INTERSECT SELECT id FROM test_table WHERE id > 0");
+    assert_eq!(int_rows(res), [0, 2, 3]);
+
+    // the types come from the query, whatever rows it returns
+    let err = try_sql(db, "SELECT id FROM test_table WHERE id < -100 UNION SELECT name FROM test_table WHERE id < -100").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::CompoundTypeMismatch(_, DataTypes::INT, DataTypes::TEXT))));
+    let res = run_sql(db, "SELECT NULL AS n FROM test_table WHERE id = 0 UNION SELECT id FROM test_table WHERE id < -100");
+    assert_eq!(res.types(), [DataTypes::INT]);
+}
+
+fn test_db_ctes(db: &mut db::DB) {
+    let text = |s: &str| DBField::Text(s.to_string());
+    let res = run_sql(db, "WITH low AS (SELECT id, name FROM test_table WHERE id < 3 AND id >= 0), named AS (SELECT name AS n FROM low WHERE id > 0) SELECT UPPER(n) AS shout FROM named UNION SELECT name FROM low WHERE id = 0");
+    assert_eq!(res.columns(), ["shout"]);
+    assert_eq!(res.into_rows(), [vec![text("ALICE")], vec![text("ROB")], vec![text("Bob")]]);
+
+    let res = run_sql(db, "WITH RECURSIVE cnt (n) AS (SELECT id FROM test_table WHERE id = 0 UNION ALL SELECT n + 1 FROM cnt WHERE n < 4) SELECT n * 10 FROM cnt").into_rows();
+    assert_eq!(res, (0..5).map(|i| vec![DBField::Int(i * 10)]).collect::<Vec<_>>());
+
+    // the descendants of 1, the parent of an id being id / 2
+    let res = run_sql(db, "WITH RECURSIVE tree AS (SELECT id FROM test_table WHERE id = 1 UNION SELECT id FROM test_table WHERE id > 0 AND id / 2 IN (SELECT id FROM tree)) SELECT DISTINCT id FROM tree").into_rows();
+    assert_eq!(res, [1, 2, 3, 4, 5, 6, 7, 8].map(|i| vec![DBField::Int(i)]));
+
+    // the columns are typed from the query, even with no rows to look at
+    let res = run_sql(db, "WITH none (n, label) AS (SELECT id, name FROM test_table WHERE id < -100) SELECT n, label FROM none");
+    assert_eq!(res.types(), [DataTypes::INT, DataTypes::TEXT]);
+    let err = try_sql(db, "WITH RECURSIVE cnt (n) AS (SELECT id FROM test_table WHERE id = 0 UNION ALL SELECT n || 'x' FROM cnt WHERE n < 2) SELECT n FROM cnt").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::CompoundTypeMismatch(_, DataTypes::INT, DataTypes::TEXT))));
+
+    let err = try_sql(db, "WITH RECURSIVE cnt (n) AS (SELECT id FROM test_table WHERE id = 0 UNION ALL SELECT n + 1 FROM cnt) SELECT n FROM cnt").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::RecursionLimit(..))));
+    let err = try_sql(db, "WITH low (a, b) AS (SELECT id FROM test_table) SELECT a FROM low").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::CteColumnCount(_, 2, 1))));
+    let err = try_sql(db, "WITH cnt AS (SELECT id FROM cnt) SELECT id FROM cnt").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::TableNotFound(_))));
+}
+
+fn test_db_windows(db: &mut db::DB) {
+    db.register_aggregate_function(
+        "total",
+        DataTypes::INT,
+        || 0,
+        |sum: &mut i32, args| match args {
+            [DBField::Int(i)] => {
+                *sum += i;
+                Ok(())
+            }
+            _ => Err("expected a single INT".to_string()),
+        },
+        |sum| Ok(DBField::Int(sum)),
+    );
+    let res = run_sql(db, "SELECT id, ROW_NUMBER() OVER (ORDER BY id DESC), RANK() OVER (ORDER BY id), DENSE_RANK() OVER (ORDER BY id), LAG(id) OVER (ORDER BY id), LEAD(id, 2, -1) OVER (PARTITION BY id % 2 ORDER BY id), total(id) OVER (ORDER BY id), total(id) OVER (PARTITION BY id % 2) FROM test_table WHERE id BETWEEN 0 AND 4").into_rows();
+    let row = |vals: [Option<i32>; 8]| vals.map(|v| v.map_or(DBField::Null, DBField::Int)).to_vec();
+    assert_eq!(res, [
+        row([Some(0), Some(6), Some(1), Some(1), None, Some(4), Some(0), Some(6)]),
+        row([Some(1), Some(4), Some(2), Some(2), Some(0), Some(3), Some(2), Some(5)]),
+        row([Some(2), Some(3), Some(4), Some(3), Some(1), Some(-1), Some(4), Some(6)]),
+        row([Some(3), Some(2), Some(5), Some(4), Some(2), Some(-1), Some(7), Some(5)]),
+        row([Some(4), Some(1), Some(6), Some(5), Some(3), Some(-1), Some(11), Some(6)]),
+        row([Some(1), Some(5), Some(2), Some(2), Some(1), Some(-1), Some(2), Some(5)]),
+    ]);
+
+    let err = try_sql(db, "SELECT id FROM test_table WHERE ROW_NUMBER() OVER () = 1").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::WindowMisuse(_)))));
+    let err = try_sql(db, "SELECT UPPER(name) OVER () FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::NotAWindowFunction(_))));
+}
+
+fn test_db_views(db: &mut db::DB) {
+    let text = |s: &str| DBField::Text(s.to_string());
+    run_sql(db, "CREATE VIEW low_ids AS SELECT id AS ident, UPPER(name) AS shout FROM test_table WHERE id BETWEEN 0 AND 2");
+    run_sql(db, "CREATE VIEW lower_ids AS SELECT ident FROM low_ids WHERE ident < 2 UNION SELECT id FROM test_table WHERE id = 5");
+    assert!(exists("low_ids.view").unwrap());
+
+    let res = run_sql(db, "SELECT v.shout FROM low_ids AS v WHERE v.ident > 0 AND v.ident IN (SELECT ident FROM lower_ids)");
+    assert_eq!(res.columns(), ["shout"]);
+    assert_eq!(res.into_rows(), [vec![text("ALICE")], vec![text("ALICE")]]);
+    let res = run_sql(db, "SELECT ident FROM lower_ids").into_rows();
+    assert_eq!(res, [0, 1, 5].map(|i| vec![DBField::Int(i)]));
+    // typed from the view's query, even when it returns no rows
+    let res = run_sql(db, "SELECT ident, shout FROM low_ids WHERE ident < -100");
+    assert_eq!(res.types(), [DataTypes::INT, DataTypes::TEXT]);
+
+    // the definitions are read back, not parsed as tables
+    let reopened = db::DB::open(".").unwrap();
+    assert_eq!(reopened.get_table_count(), 1);
+    assert!(reopened.get_view("lower_ids").is_some());
+
+    let err = try_sql(db, "CREATE VIEW test_table AS SELECT id FROM test_table").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::NameInUse(_))));
+    let err = try_sql(db, "CREATE VIEW broken AS SELECT id FROM missing").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::TableNotFound(_))));
+    let err = try_sql(db, "INSERT INTO low_ids VALUES (1, 'x')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ViewNotWritable(_))));
+
+    run_sql(db, "DROP VIEW lower_ids");
+    run_sql(db, "DROP VIEW low_ids");
+    assert!(!exists("low_ids.view").unwrap());
+    let err = try_sql(db, "SELECT ident FROM low_ids").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::TableNotFound(_))));
+    let err = try_sql(db, "DROP VIEW low_ids").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::ViewNotFound(_))));
+}
+
+fn test_db_materialized_views(db: &mut db::DB) {
+    run_sql(db, "CREATE MATERIALIZED VIEW id_stats AS SELECT total(id) AS id_sum, 'ids' AS label FROM test_table WHERE id BETWEEN 0 AND 30");
+    assert!(exists("id_stats.txt").unwrap() && exists("id_stats.mview").unwrap());
+    assert_eq!(db.get_table_count(), 2);
+    let before = run_sql(db, "SELECT id_sum, label FROM id_stats").into_rows();
+    let DBField::Int(sum) = before[0][0] else { panic!("expected an INT sum") };
+    assert_eq!(before[0][1], DBField::Text("ids".to_string()));
+
+    run_sql(db, "INSERT INTO test_table VALUES (30, 'Zed')");
+    assert_eq!(run_sql(db, "SELECT id_sum FROM id_stats").into_rows(), [vec![DBField::Int(sum)]]);
+    run_sql(db, "REFRESH MATERIALIZED VIEW id_stats");
+    assert_eq!(run_sql(db, "SELECT id_sum FROM id_stats").into_rows(), [vec![DBField::Int(sum + 30)]]);
+
+    // both the rows and the query survive a reopen
+    let reopened = db::DB::open(".").unwrap();
+    assert_eq!(reopened.get_table_count(), 2);
+    assert!(reopened.get_view("id_stats").is_some_and(|v| v.materialized));
+
+    let err = try_sql(db, "INSERT INTO id_stats VALUES (1, 'x')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ViewNotWritable(_))));
+
+    // the columns are typed after the query, so a view made from no rows takes
+    // those refreshed later, NULL included
+    File::create("readings.txt").unwrap().write_all(b"id: INT, value: INT\n").unwrap();
+    *db = db::DB::open(".").unwrap();
+    run_sql(db, "CREATE MATERIALIZED VIEW shifted AS SELECT id + 1 AS n, NULLIF(value, 0) AS v FROM readings WHERE id > 1000");
+    assert_eq!(run_sql(db, "SELECT n, v FROM shifted").types(), [DataTypes::INT, DataTypes::INT]);
+    run_sql(db, "INSERT INTO readings VALUES (1001, 0), (1002, 5)");
+    let res = run_sql(db, "REFRESH MATERIALIZED VIEW shifted");
+    assert_eq!(res.rows_affected(), 2);
+    let expected = [vec![DBField::Int(1002), DBField::Null], vec![DBField::Int(1003), DBField::Int(5)]];
+    assert_eq!(run_sql(db, "SELECT n, v FROM shifted").into_rows(), expected);
+    let reopened = db::DB::open(".").unwrap();
+    assert_eq!(reopened.query("SELECT n, v FROM shifted").unwrap().into_rows(), expected);
+    run_sql(db, "DROP MATERIALIZED VIEW shifted");
+    remove_file("readings.txt").unwrap();
+
+    // a TEXT spelling out NULL or its marker is not taken for one once stored
+    File::create("notes.txt").unwrap().write_all(b"id: INT, body: TEXT\n1, NULL\n2, \\N\n3, none\n").unwrap();
+    *db = db::DB::open(".").unwrap();
+    run_sql(db, "CREATE MATERIALIZED VIEW kept AS SELECT id, NULLIF(body, 'none') AS body FROM notes");
+    let text = |s: &str| DBField::Text(s.to_string());
+    let expected = [vec![DBField::Int(1), text("NULL")], vec![DBField::Int(2), text("\\N")], vec![DBField::Int(3), DBField::Null]];
+    let reopened = db::DB::open(".").unwrap();
+    assert_eq!(reopened.query("SELECT id, body FROM kept").unwrap().into_rows(), expected);
+    run_sql(db, "DROP MATERIALIZED VIEW kept");
+    remove_file("notes.txt").unwrap();
+    *db = db::DB::open(".").unwrap();
+
+    run_sql(db, "DROP MATERIALIZED VIEW id_stats");
+    assert!(!exists("id_stats.txt").unwrap() && !exists("id_stats.mview").unwrap());
+    assert_eq!(db.get_table_count(), 1);
+    let err = try_sql(db, "REFRESH MATERIALIZED VIEW id_stats").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::ViewNotFound(_))));
+}
+
+fn test_db_triggers(db: &mut db::DB) {
+    let logged = |id: i32, note: &str| vec![DBField::Int(id), DBField::Text(note.to_string())];
+    File::create("audit.txt").unwrap().write_all(b"id: INT, note: TEXT\n").unwrap();
+    *db = db::DB::open(".").unwrap();
+
+    run_sql(db, "CREATE TRIGGER log_before BEFORE INSERT ON test_table FOR EACH ROW INSERT INTO audit VALUES (NEW.id, 'before ' || NEW.name)");
+    // the inserted row is already stored when an AFTER trigger runs
+    run_sql(db, "CREATE TRIGGER log_after AFTER INSERT ON test_table FOR EACH ROW INSERT INTO audit SELECT id, 'after' FROM test_table WHERE id = NEW.id");
+    assert!(exists("log_before.trigger").unwrap());
+    run_sql(db, "INSERT INTO test_table VALUES (40, 'Trig'), (41, 'Ger')");
+    assert_eq!(run_sql(db, "SELECT id, note FROM audit").into_rows(), [
+        logged(40, "before Trig"), logged(41, "before Ger"), logged(40, "after"), logged(41, "after"),
+    ]);
+
+    let reopened = db::DB::open(".").unwrap();
+    assert!(reopened.get_trigger("log_after").is_some());
+
+    let err = try_sql(db, "CREATE TRIGGER log_before AFTER INSERT ON test_table FOR EACH ROW SELECT id FROM audit").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::TriggerExists(_))));
+    let err = try_sql(db, "CREATE TRIGGER bad AFTER INSERT ON test_table FOR EACH ROW INSERT INTO audit VALUES (NEW.age, 'x')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ColumnNotFound(_))));
+    let err = try_sql(db, "CREATE TRIGGER bad AFTER INSERT ON missing FOR EACH ROW SELECT id FROM audit").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::TableNotFound(_))));
+
+    run_sql(db, "DROP TRIGGER log_before");
+    run_sql(db, "DROP TRIGGER log_after");
+    assert!(!exists("log_before.trigger").unwrap());
+    let err = try_sql(db, "DROP TRIGGER log_after").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::TriggerNotFound(_))));
+
+    // a trigger inserting into its own table keeps setting itself off, which
+    // undoes the insert along with all the rows the trigger wrote
+    let logged_before = read_to_string("audit.txt").unwrap();
+    run_sql(db, "CREATE TRIGGER echo AFTER INSERT ON audit FOR EACH ROW INSERT INTO audit VALUES (NEW.id + 1, 'echo')");
+    let err = try_sql(db, "INSERT INTO audit VALUES (0, 'start')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::TriggerDepth(_, _))));
+    assert_eq!(run_sql(db, "SELECT id FROM audit").into_rows().len(), 4);
+    assert_eq!(read_to_string("audit.txt").unwrap(), logged_before);
+    run_sql(db, "DROP TRIGGER echo");
+
+    // BEFORE triggers only run for the rows written, and their writes are
+    // undone if the insert itself fails
+    File::create("members.txt").unwrap().write_all(b"id: INT PRIMARY KEY, name: TEXT\n1, Ann\n").unwrap();
+    *db = db::DB::open(".").unwrap();
+    run_sql(db, "CREATE TRIGGER greet BEFORE INSERT ON members FOR EACH ROW INSERT INTO audit VALUES (NEW.id, 'hi ' || NEW.name)");
+    run_sql(db, "INSERT INTO members VALUES (1, 'Again'), (2, 'Bo'), (2, 'Twice') ON CONFLICT (id) DO NOTHING");
+    assert_eq!(run_sql(db, "SELECT id, note FROM audit WHERE id = 1 OR id = 2").into_rows(), [logged(2, "hi Bo")]);
+    let logged_before = read_to_string("audit.txt").unwrap();
+    let err = try_sql(db, "INSERT INTO members VALUES (3, 'Cy'), (3, 'Cy')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::UniqueViolation(_, _))));
+    assert_eq!(read_to_string("audit.txt").unwrap(), logged_before);
+    assert_eq!(run_sql(db, "SELECT id FROM members").into_rows(), [vec![DBField::Int(1)], vec![DBField::Int(2)]]);
+    // only the tables the triggers may write to are rolled back
+    assert_eq!(db.trigger_targets("members"), ["members", "audit"]);
+
+    // inserts not going through the database can not run the triggers, so they are refused
+    let tb = db.get_mut_table("members").unwrap();
+    let err = tb.insert(None, vec![DBField::Int(4), DBField::Text("Di".to_string())]).unwrap_err();
+    assert!(matches!(err, DBError::TriggersNotRun(_)));
+    let crate::ASTNode::Statment(statment) = sql::parser::parse_sql("INSERT INTO members VALUES (4, 'Di')").unwrap().first_node;
+    let engine = engine::Engine::new(sql::parser::parse_sql("INSERT INTO members VALUES (4, 'Di')").unwrap());
+    let err = engine.run_on_table(tb, &statment).unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::TriggersNotRun(_))));
+    run_sql(db, "DROP TRIGGER greet");
+    let tb = db.get_mut_table("members").unwrap();
+    tb.insert(None, vec![DBField::Int(4), DBField::Text("Di".to_string())]).unwrap();
+    remove_file("members.txt").unwrap();
+    remove_file("audit.txt").unwrap();
+    *db = db::DB::open(".").unwrap();
+}
+
+fn test_db_generated_columns(db: &mut db::DB) {
+    let int = DBField::Int;
+    let text = |s: &str| DBField::Text(s.to_string());
+    let header = "id: INT PRIMARY KEY, price: INT, qty: INT, \
+        total: INT GENERATED ALWAYS AS (price * qty) STORED, \
+        label: TEXT GENERATED ALWAYS AS (CAST(id AS TEXT) || ', x') VIRTUAL, \
+        doubled: INT GENERATED ALWAYS AS (total * 2) VIRTUAL";
+    // the virtual columns are not in the file
+    File::create("orders.txt").unwrap().write_all(format!("{}\n1, 10, 2, 20\n", header).as_bytes()).unwrap();
+    *db = db::DB::open(".").unwrap();
+
+    let res = run_sql(db, "INSERT INTO orders (id, price, qty) VALUES (2, 5, 3) RETURNING *").into_rows();
+    assert_eq!(res, [vec![int(2), int(5), int(3), int(15), text("2, x"), int(30)]]);
+    run_sql(db, "INSERT INTO orders VALUES (3, 4, 4)");
+    let res = run_sql(db, "SELECT id, total, doubled FROM orders WHERE doubled > 30").into_rows();
+    assert_eq!(res, [vec![int(1), int(20), int(40)], vec![int(3), int(16), int(32)]]);
+    let contents = read_to_string("orders.txt").unwrap();
+    assert_eq!(contents.lines().next(), Some(header));
+    assert!(contents.lines().any(|l| l == "2, 5, 3, 15"));
+
+    let res = run_sql(db, "INSERT INTO orders (id, price, qty) VALUES (2, 6, 3) ON CONFLICT (id) DO UPDATE SET price = excluded.price RETURNING total, doubled").into_rows();
+    assert_eq!(res, [vec![int(18), int(36)]]);
+    let err = try_sql(db, "INSERT INTO orders (id, total) VALUES (4, 1)").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::GeneratedColumnWrite(_))));
+    let err = try_sql(db, "INSERT INTO orders (id, price, qty) VALUES (1, 1, 1) ON CONFLICT DO UPDATE SET total = 1").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::GeneratedColumnWrite(_))));
+
+    let reopened = db::DB::open(".").unwrap();
+    let tb = reopened.get_table("orders").unwrap();
+    assert_eq!(tb.select_cols(vec!["label", "doubled"]).unwrap(), [vec![text("1, x"), int(40)], vec![text("2, x"), int(36)], vec![text("3, x"), int(32)]]);
+
+    // generated columns may only read the generated ones before them
+    File::create("broken.txt").unwrap().write_all(b"a: INT, b: INT GENERATED ALWAYS AS (c + 1), c: INT GENERATED ALWAYS AS (a) STORED\n").unwrap();
+    assert!(db::DB::open(".").is_err());
+    remove_file("broken.txt").unwrap();
+    remove_file("orders.txt").unwrap();
+
+    // generated columns may call the functions registered on the database
+    File::create("tags.txt").unwrap().write_all(b"name: TEXT, first: TEXT GENERATED ALWAYS AS (initial(name)) STORED, \
+        shout: TEXT GENERATED ALWAYS AS (UPPER(initial(name))) VIRTUAL\n").unwrap();
+    *db = db::DB::open(".").unwrap();
+    db.register_scalar_function("initial", DataTypes::TEXT, |args| match args {
+        [DBField::Text(s)] => Ok(DBField::Text(s.chars().take(1).collect())),
+        _ => Err("expected a single TEXT".to_string()),
+    });
+    run_sql(db, "INSERT INTO tags (name) VALUES ('rust')");
+    let res = db.query("SELECT first, shout FROM tags").unwrap().into_rows();
+    assert_eq!(res, [vec![text("r"), text("R")]]);
+    remove_file("tags.txt").unwrap();
+}
+
+
+fn test_db_scripts(db: &mut db::DB) {
+    let script = sql::parser::parse_script("
+        INSERT INTO test_table VALUES (50, 'Script');
+        SELECT name FROM test_table WHERE id = 50;
+        CREATE VIEW scripted AS SELECT id FROM test_table WHERE id >= 50;
+    ").unwrap();
+    let results = engine::run_script(script, db).unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].columns().is_empty() && results[0].rows().is_empty());
+    let rows = results[1].rows();
+    assert_eq!(rows, [vec![DBField::Text("Script".to_string())]]);
+
+    // the statments before the failing one stay applied, the ones after are not run
+    let script = sql::parser::parse_script("
+        INSERT INTO test_table VALUES (51, 'Kept');
+        SELECT missing FROM test_table;
+        INSERT INTO test_table VALUES (52, 'Skipped')
+    ").unwrap();
+    let err = engine::run_script(script, db).unwrap_err();
+    assert_eq!(err.index(), Some(1));
+    let rows = run_sql(db, "SELECT id FROM scripted").into_rows();
+    assert_eq!(rows, [vec![DBField::Int(50)], vec![DBField::Int(51)]]);
+    run_sql(db, "DROP VIEW scripted");
+}
+
+fn test_db_quoted_identifiers(db: &mut db::DB) {
+    run_sql(db, "CREATE VIEW quoted AS SELECT id AS \"order\", name AS \"full name\" FROM test_table -- ids below 2\n WHERE id < 2");
+    // the saved definition parses back with its quoted names
+    let reopened = db::DB::open(".").unwrap();
+    assert!(reopened.get_view("quoted").is_some());
+    let res = run_sql(db, "SELECT \"full name\" FROM quoted WHERE \"order\" = 1 /* Alice */");
+    assert_eq!(res.columns(), ["full name"]);
+    let rows = res.into_rows();
+    assert_eq!(rows[0], vec![DBField::Text("Alice".to_string())]);
+    run_sql(db, "DROP VIEW quoted");
+}
+
+fn test_db_prepared_statements(db: &mut db::DB) {
+    let insert = PreparedStatement::new("INSERT INTO test_table VALUES (?, ?)").unwrap();
+    insert.execute(db, &[DBField::Int(60), DBField::Text("Grace".to_string())]).unwrap();
+    // the value is stored as is, never read as sql
+    insert.execute(db, &[DBField::Int(61), DBField::Text("x'); DROP VIEW v; --".to_string())]).unwrap();
+
+    let select = PreparedStatement::new("SELECT name FROM test_table WHERE id >= :low AND id <= :low + 1").unwrap();
+    let rows = select.execute_named(db, &[("low", DBField::Int(60))]).unwrap().into_rows();
+    assert_eq!(rows, [vec![DBField::Text("Grace".to_string())], vec![DBField::Text("x'); DROP VIEW v; --".to_string())]]);
+
+    let err = insert.execute(db, &[DBField::Text("62".to_string()), DBField::Text("Heidi".to_string())]).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ParameterType(..))));
+    let err = select.execute_named(db, &[("low", DBField::Text("60".to_string()))]).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ParameterType(..))));
+    let err = insert.execute(db, &[DBField::Int(62)]).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ParameterCount(2, 1))));
+    let err = select.execute_named(db, &[("high", DBField::Int(60))]).unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::UnboundParameter(_))));
+    // columns of views, CTEs and aliased tables are typed as well
+    run_sql(db, "CREATE VIEW graced AS SELECT id + 0 AS n, name FROM test_table WHERE id >= 60");
+    let text = [DBField::Text("60".to_string())];
+    for sql_s in [
+        "SELECT name FROM graced WHERE n = ?",
+        "WITH low (m) AS (SELECT n FROM graced) SELECT m FROM low WHERE m = ?",
+        "SELECT t.name FROM test_table AS t WHERE t.id = ?",
+    ] {
+        let err = PreparedStatement::new(sql_s).unwrap().execute(db, &text).unwrap_err();
+        assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::ParameterType(..))), "{}", sql_s);
+    }
+    let rows = PreparedStatement::new("SELECT name FROM graced WHERE n = ?").unwrap().execute(db, &[DBField::Int(60)]).unwrap().into_rows();
+    assert_eq!(rows, [vec![DBField::Text("Grace".to_string())]]);
+    run_sql(db, "DROP VIEW graced");
+
+    // placeholders left unbound by running the sql directly
+    let err = try_sql(db, "SELECT name FROM test_table WHERE id = $1").unwrap_err();
+    assert!(matches!(err.downcast_ref::<DBError>(), Some(DBError::InvalidExpression(EngineError::UnboundParameter(_)))));
+}
+
+fn test_db_execute_and_query(db: &mut db::DB) {
+    db.execute("INSERT INTO test_table VALUES (70, 'Ivan')").unwrap();
+    // one borrowed database serves queries one after the other
+    for _ in 0..2 {
+        let rows = db.query("SELECT name FROM test_table WHERE id = 70").unwrap().into_rows();
+        assert_eq!(rows, [vec![DBField::Text("Ivan".to_string())]]);
+    }
+    let err = db.query("INSERT INTO test_table VALUES (71, 'Judy')").unwrap_err();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::NotReadOnly(_))));
+    let err = db.query("SELECT name FROM").unwrap_err();
+    assert!(err.downcast_ref::<sql::errors::ParseError>().is_some());
+    // a second statment or a broken filter is refused rather than dropped
+    let err = db.execute("INSERT INTO test_table VALUES (71, 'Judy'); INSERT INTO test_table VALUES (72, 'Ken')").unwrap_err();
+    assert!(err.downcast_ref::<sql::errors::ParseError>().is_some());
+    let err = db.query("SELECT name FROM test_table WHERE id = )").unwrap_err();
+    assert!(err.downcast_ref::<sql::errors::ParseError>().is_some());
+    assert!(db.query("SELECT name FROM test_table WHERE id = 70;").is_ok());
+    let rows = db.query("SELECT id FROM test_table WHERE id = 71").unwrap().into_rows();
+    assert!(rows.is_empty());
+}
+
+fn test_db_result_metadata(db: &mut db::DB) {
+    let res = run_sql(db, "INSERT INTO test_table VALUES (80, 'Kim'), (81, 'Lee')");
+    assert_eq!(res.rows_affected(), 2);
+    assert!(res.columns().is_empty() && res.rows().is_empty());
+    let res = run_sql(db, "INSERT INTO test_table VALUES (81, 'Lee'), (82, 'Max') ON CONFLICT (id) DO NOTHING RETURNING id");
+    assert_eq!(res.rows_affected(), 1);
+    assert_eq!(res.types(), [DataTypes::INT]);
+    assert_eq!(res.rows(), [vec![DBField::Int(82)]]);
+
+    let table_rows = db.get_table(TEST_TABLE_NAME).unwrap().row_count();
+    let res = db.query("SELECT id, UPPER(name) AS shout, NULL FROM test_table WHERE id BETWEEN 80 AND 81").unwrap();
+    assert_eq!(res.columns(), ["id", "shout", "NULL"]);
+    assert_eq!(res.types(), [DataTypes::INT, DataTypes::TEXT, DataTypes::TEXT]);
+    assert_eq!(res.rows().len(), 2);
+    assert_eq!((res.rows_affected(), res.rows_scanned()), (0, table_rows));
+    // declared types hold without any row to go by
+    let res = db.query("SELECT id FROM test_table WHERE id = 999").unwrap();
+    assert_eq!(res.types(), [DataTypes::INT]);
+    assert!(res.rows().is_empty());
+    // the subquery reads the table once more
+    let res = db.query("SELECT name FROM test_table WHERE id IN (SELECT id FROM test_table WHERE id = 82)").unwrap();
+    assert_eq!(res.rows_scanned(), 2 * table_rows);
+}
+
+fn test_db_query_iter(db: &mut db::DB) {
+    let eager = db.query("SELECT t.id, name FROM test_table AS t WHERE id BETWEEN 80 AND 82").unwrap();
+    let mut cursor = db.query_iter("SELECT t.id, name FROM test_table AS t WHERE id BETWEEN 80 AND 82").unwrap();
+    assert_eq!(cursor.columns(), eager.columns());
+    assert_eq!(cursor.types(), [DataTypes::INT, DataTypes::TEXT]);
+    assert_eq!(cursor.by_ref().take(1).collect::<Result<Vec<_>, _>>().unwrap(), eager.rows()[..1]);
+    assert_eq!(cursor.collect::<Result<Vec<_>, _>>().unwrap(), eager.rows()[1..]);
+
+    // typed the same way as the eager result
+    let sql_s = "SELECT id + 1, UPPER(name) FROM test_table WHERE id = 80";
+    let cursor = db.query_iter(sql_s).unwrap();
+    assert_eq!(cursor.types(), db.query(sql_s).unwrap().types());
+    assert_eq!(cursor.types(), [DataTypes::INT, DataTypes::TEXT]);
+
+    // the rows are evaluated as the cursor gets to them: stopping before the
+    // row dividing by zero never fails
+    let sql_s = "SELECT id FROM test_table WHERE 10 / (id - 5) > -100";
+    assert!(db.query(sql_s).is_err());
+    let first = db.query_iter(sql_s).unwrap().next().unwrap().unwrap();
+    assert_eq!(first, vec![DBField::Int(0)]);
+    let mut cursor = db.query_iter(sql_s).unwrap();
+    let err = cursor.by_ref().find_map(|row| row.err()).unwrap();
+    assert!(matches!(err, DBError::InvalidExpression(EngineError::DivisionByZero)));
+    assert!(cursor.next().is_none());
+
+    // queries that can not be run lazily hand out their rows all the same
+    let cursor = db.query_iter("SELECT id FROM test_table WHERE id > 80 UNION SELECT id FROM test_table WHERE id = 0").unwrap();
+    assert_eq!(cursor.types(), [DataTypes::INT]);
+    assert_eq!(cursor.count(), 3);
+
+    // a condition failing on a row is an error, not a match
+    let tb = db.get_table(TEST_TABLE_NAME).unwrap();
+    let where_exprs = sql::parser::parse_expr_sql("10 / (id - 5) > -100").unwrap();
+    let engine = engine::Engine::new(sql::parser::parse_sql("SELECT id FROM test_table").unwrap());
+    let mut scan = tb.scan_where(vec!["id".to_string()], vec![where_exprs], &engine).unwrap();
+    assert!(scan.any(|row| matches!(row, Err(DBError::InvalidExpression(EngineError::DivisionByZero)))));
+    let err = db.query_iter("INSERT INTO test_table VALUES (90, 'Nope')").err().unwrap();
+    assert!(matches!(err.downcast_ref::<EngineError>(), Some(EngineError::NotReadOnly(_))));
+}
@@ -0,0 +1,48 @@
+use crate::database::DBField;
+use crate::sql::functions::builtin;
+use crate::sql_errors::EngineError;
+
+fn call(name: &str, args: &[DBField]) -> Result<DBField, EngineError> {
+    builtin(name).unwrap()(args)
+}
+
+fn text(s: &str) -> DBField {
+    DBField::Text(s.to_string())
+}
+
+#[test]
+fn test_text_functions() {
+    assert_eq!(call("LENGTH", &[text("Alice")]).unwrap(), DBField::Int(5));
+    assert_eq!(call("UPPER", &[text("Alice")]).unwrap(), text("ALICE"));
+    assert_eq!(call("LOWER", &[text("Alice")]).unwrap(), text("alice"));
+    assert_eq!(call("TRIM", &[text("  Bob ")]).unwrap(), text("Bob"));
+    assert_eq!(call("SUBSTR", &[text("Alice"), DBField::Int(2)]).unwrap(), text("lice"));
+    assert_eq!(call("SUBSTR", &[text("Alice"), DBField::Int(2), DBField::Int(3)]).unwrap(), text("lic"));
+    assert_eq!(call("SUBSTR", &[text("Alice"), DBField::Int(0), DBField::Int(2)]).unwrap(), text("A"));
+    assert_eq!(call("REPLACE", &[text("Rob"), text("R"), text("B")]).unwrap(), text("Bob"));
+    assert_eq!(call("UPPER", &[DBField::Null]).unwrap(), DBField::Null);
+}
+
+#[test]
+fn test_numeric_functions() {
+    assert_eq!(call("ABS", &[DBField::Int(-4)]).unwrap(), DBField::Int(4));
+    assert_eq!(call("ROUND", &[DBField::Int(1250), DBField::Int(-2)]).unwrap(), DBField::Int(1300));
+    assert_eq!(call("ROUND", &[DBField::Int(-1249), DBField::Int(-2)]).unwrap(), DBField::Int(-1200));
+    assert_eq!(call("ROUND", &[DBField::Int(7)]).unwrap(), DBField::Int(7));
+}
+
+#[test]
+fn test_null_functions() {
+    assert_eq!(call("COALESCE", &[DBField::Null, DBField::Int(1), DBField::Int(2)]).unwrap(), DBField::Int(1));
+    assert_eq!(call("COALESCE", &[DBField::Null]).unwrap(), DBField::Null);
+    assert_eq!(call("NULLIF", &[DBField::Int(1), DBField::Int(1)]).unwrap(), DBField::Null);
+    assert_eq!(call("NULLIF", &[DBField::Int(1), DBField::Int(2)]).unwrap(), DBField::Int(1));
+}
+
+#[test]
+fn test_function_errors() {
+    assert!(matches!(call("LENGTH", &[]), Err(EngineError::FunctionArity(_, 0))));
+    assert!(matches!(call("LENGTH", &[DBField::Int(1)]), Err(EngineError::FunctionArgumentType(..))));
+    assert!(matches!(call("ABS", &[DBField::Int(i32::MIN)]), Err(EngineError::FunctionArgumentType(..))));
+    assert!(builtin("NO_SUCH_FUNCTION").is_none());
+}
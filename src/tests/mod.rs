@@ -2,4 +2,10 @@
 pub mod db_test;
 #[cfg(test)]
 pub mod parser_tests;
+#[cfg(test)]
+pub mod pattern_tests;
+#[cfg(test)]
+pub mod function_tests;
+#[cfg(test)]
+pub mod window_tests;
 mod constants;
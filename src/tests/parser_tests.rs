@@ -1,13 +1,14 @@
 use crate::sql;
 use crate::SqlToken;
 use crate::SqlKeyword;
+use crate::database::DataTypes;
+use crate::{ASTNode, Parameter, ConflictAction, Operator, Expr, InsertSource, InsertStatement, Literal, OnConflict, Parser, SelectItem, SelectStatement, SetOperator, Statement, TriggerTiming};
 
 #[test]
 fn test_lexing() {
     let sql_s = "SELECT name FROM test_table";
     let mut lx = sql::lexer::Lexer {
         input: sql_s.chars().peekable(),
-        prev_token: SqlToken::EOF,
     };
     let tokens = match lx.lex() {
         Ok(tkns) => tkns,
@@ -21,3 +22,426 @@ fn test_lexing() {
     ];
     assert_eq!(tokens, expected_vec);
 }
+
+fn parse(sql_s: &str) -> Statement {
+    let mut lx = sql::lexer::Lexer {
+        input: sql_s.chars().peekable(),
+    };
+    let tokens = lx.lex().unwrap();
+    let ASTNode::Statment(statment) = Parser::new(tokens).generate_ast().unwrap().first_node;
+    statment
+}
+
+fn item(expr: Expr) -> SelectItem {
+    SelectItem {expr, alias: None}
+}
+
+fn projections(select: &SelectStatement) -> Vec<Expr> {
+    select.columns.iter().map(|c| c.expr.clone()).collect()
+}
+
+#[test]
+fn test_parse_insert_multiple_values() {
+    let statment = parse("INSERT INTO test_table (id, name) VALUES (6, 'Eve'), (7, 'Mallory')");
+    let expected = Statement::Insert(InsertStatement {
+        table: "test_table".to_string(),
+        columns: Some(vec!["id".to_string(), "name".to_string()]),
+        source: InsertSource::Values(vec![
+            vec![Expr::Literal(Literal::Number(6)), Expr::Literal(Literal::String("Eve".to_string()))],
+            vec![Expr::Literal(Literal::Number(7)), Expr::Literal(Literal::String("Mallory".to_string()))],
+        ]),
+        on_conflict: None,
+        returning: None,
+    });
+    assert_eq!(statment, expected);
+}
+
+#[test]
+fn test_parse_insert_select() {
+    let statment = parse("INSERT INTO test_table (id, name) SELECT id, name FROM other_table");
+    let expected = Statement::Insert(InsertStatement {
+        table: "test_table".to_string(),
+        columns: Some(vec!["id".to_string(), "name".to_string()]),
+        source: InsertSource::Select(Box::new(SelectStatement {
+            distinct: false,
+            columns: vec![item(Expr::Identifier("id".to_string())), item(Expr::Identifier("name".to_string()))],
+            table: "other_table".to_string(),
+            table_alias: None,
+            where_clause: None,
+        })),
+        on_conflict: None,
+        returning: None,
+    });
+    assert_eq!(statment, expected);
+}
+
+#[test]
+fn test_parse_insert_without_columns() {
+    let statment = parse("INSERT INTO test_table VALUES (6, 'Eve')");
+    let expected = Statement::Insert(InsertStatement {
+        table: "test_table".to_string(),
+        columns: None,
+        source: InsertSource::Values(vec![
+            vec![Expr::Literal(Literal::Number(6)), Expr::Literal(Literal::String("Eve".to_string()))],
+        ]),
+        on_conflict: None,
+        returning: None,
+    });
+    assert_eq!(statment, expected);
+}
+
+#[test]
+fn test_parse_insert_on_conflict() {
+    let statment = parse("INSERT INTO test_table VALUES (0, 'Bobby') ON CONFLICT (id) DO UPDATE SET name = excluded.name");
+    let Statement::Insert(insert) = statment else { panic!("expected an insert") };
+    assert_eq!(insert.on_conflict, Some(OnConflict {
+        target: Some("id".to_string()),
+        action: ConflictAction::Update(vec![("name".to_string(), Expr::QualifiedIdentifier {qualifier: "excluded".to_string(), name: "name".to_string()})]),
+    }));
+
+    let statment = parse("INSERT INTO test_table VALUES (0, 'Bobby') ON CONFLICT DO NOTHING");
+    let Statement::Insert(insert) = statment else { panic!("expected an insert") };
+    assert_eq!(insert.on_conflict, Some(OnConflict {target: None, action: ConflictAction::Nothing}));
+}
+
+fn binary(left: Expr, op: Operator, right: Expr) -> Expr {
+    Expr::Binary {left: Box::new(left), op, right: Box::new(right)}
+}
+
+#[test]
+fn test_parse_arithmetic_precedence() {
+    let statment = parse("SELECT name || -id * (2 + 3) - 1 FROM test_table WHERE id % 2 = -1");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let id = || Expr::Identifier("id".to_string());
+    let num = |n| Expr::Literal(Literal::Number(n));
+    assert_eq!(projections(&select), vec![binary(
+        Expr::Identifier("name".to_string()),
+        Operator::Concat,
+        binary(
+            binary(
+                Expr::Unary {op: Operator::Minus, expr: Box::new(id())},
+                Operator::Multiply,
+                binary(num(2), Operator::Plus, num(3)),
+            ),
+            Operator::Minus,
+            num(1),
+        ),
+    )]);
+    assert_eq!(select.where_clause, Some(vec![binary(
+        binary(id(), Operator::Modulo, num(2)),
+        Operator::Equal,
+        num(-1),
+    )]));
+}
+
+#[test]
+fn test_lexing_comparison_operators() {
+    let mut lx = sql::lexer::Lexer {
+        input: "<= >= <> != < > =".chars().peekable(),
+    };
+    let expected_vec = vec![
+        SqlToken::Operator(Operator::SmallerEqual),
+        SqlToken::Operator(Operator::GreaterEqual),
+        SqlToken::Operator(Operator::NotEqual),
+        SqlToken::Operator(Operator::NotEqual),
+        SqlToken::Operator(Operator::Smaller),
+        SqlToken::Operator(Operator::Greater),
+        SqlToken::Operator(Operator::Equal),
+        SqlToken::EOF,
+    ];
+    assert_eq!(lx.lex().unwrap(), expected_vec);
+}
+
+#[test]
+fn test_parse_logical_precedence() {
+    let statment = parse("SELECT id FROM test_table WHERE id = 1 OR NOT id <> 2 AND (name >= 'a' OR id <= 3)");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let id = || Expr::Identifier("id".to_string());
+    let num = |n| Expr::Literal(Literal::Number(n));
+    assert_eq!(select.where_clause, Some(vec![binary(
+        binary(id(), Operator::Equal, num(1)),
+        Operator::Or,
+        binary(
+            Expr::Unary {op: Operator::Not, expr: Box::new(binary(id(), Operator::NotEqual, num(2)))},
+            Operator::And,
+            binary(
+                binary(Expr::Identifier("name".to_string()), Operator::GreaterEqual, Expr::Literal(Literal::String("a".to_string()))),
+                Operator::Or,
+                binary(id(), Operator::SmallerEqual, num(3)),
+            ),
+        ),
+    )]));
+}
+
+#[test]
+fn test_parse_not_like_escape() {
+    let statment = parse("SELECT id FROM test_table WHERE name NOT LIKE 'A!%' ESCAPE '!' AND name ILIKE 'b%' || 'o'");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let name = || Expr::Identifier("name".to_string());
+    let text = |s: &str| Expr::Literal(Literal::String(s.to_string()));
+    assert_eq!(select.where_clause, Some(vec![binary(
+        Expr::Unary {op: Operator::Not, expr: Box::new(binary(name(), Operator::Like(Some('!')), text("A!%")))},
+        Operator::And,
+        binary(name(), Operator::ILike(None), binary(text("b%"), Operator::Concat, text("o"))),
+    )]));
+}
+
+#[test]
+fn test_parse_in_between_exists() {
+    let statment = parse("SELECT id FROM test_table WHERE id NOT IN (1, 2) AND id BETWEEN 0 AND 2 + 3 AND name IN (SELECT name FROM other_table) OR EXISTS (SELECT id FROM other_table)");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let id = || Box::new(Expr::Identifier("id".to_string()));
+    let num = |n| Expr::Literal(Literal::Number(n));
+    let sub = |col: &str| Box::new(SelectStatement {
+        distinct: false,
+        columns: vec![item(Expr::Identifier(col.to_string()))],
+        table: "other_table".to_string(),
+        table_alias: None,
+        where_clause: None,
+    });
+    assert_eq!(select.where_clause, Some(vec![binary(
+        binary(
+            binary(
+                Expr::Unary {op: Operator::Not, expr: Box::new(Expr::InList {expr: id(), list: vec![num(1), num(2)]})},
+                Operator::And,
+                Expr::Between {expr: id(), low: Box::new(num(0)), high: Box::new(binary(num(2), Operator::Plus, num(3)))},
+            ),
+            Operator::And,
+            Expr::InSubquery {expr: Box::new(Expr::Identifier("name".to_string())), subquery: sub("name")},
+        ),
+        Operator::Or,
+        Expr::Exists(sub("id")),
+    )]));
+}
+
+#[test]
+fn test_parse_function_calls() {
+    let statment = parse("SELECT upper(name), CAST(id AS TEXT), coalesce(NULL, 1) FROM test_table");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    assert_eq!(projections(&select), vec![
+        Expr::Function {name: "UPPER".to_string(), args: vec![Expr::Identifier("name".to_string())], star: false},
+        Expr::Cast {expr: Box::new(Expr::Identifier("id".to_string())), data_type: DataTypes::TEXT},
+        Expr::Function {name: "COALESCE".to_string(), args: vec![Expr::Literal(Literal::Null), Expr::Literal(Literal::Number(1))], star: false},
+    ]);
+}
+
+#[test]
+fn test_parse_case() {
+    let statment = parse("SELECT CASE id WHEN 1 THEN 'one' ELSE 'many' END, CASE WHEN id > 2 THEN 'big' END FROM test_table");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let id = || Expr::Identifier("id".to_string());
+    let num = |n| Expr::Literal(Literal::Number(n));
+    let text = |s: &str| Expr::Literal(Literal::String(s.to_string()));
+    assert_eq!(projections(&select), vec![
+        Expr::Case {
+            operand: Some(Box::new(id())),
+            branches: vec![(num(1), text("one"))],
+            else_result: Some(Box::new(text("many"))),
+        },
+        Expr::Case {
+            operand: None,
+            branches: vec![(binary(id(), Operator::Greater, num(2)), text("big"))],
+            else_result: None,
+        },
+    ]);
+}
+
+#[test]
+fn test_parse_aliases() {
+    let statment = parse("SELECT t.id AS ident, name n, id + 1 FROM test_table AS t WHERE t.id > 1");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    assert_eq!(select.table_alias, Some("t".to_string()));
+    let aliases: Vec<_> = select.columns.iter().map(|c| c.alias.clone()).collect();
+    assert_eq!(aliases, vec![Some("ident".to_string()), Some("n".to_string()), None]);
+    let names: Vec<_> = select.columns.iter().map(|c| c.output_name()).collect();
+    assert_eq!(names, vec!["ident", "n", "id + 1"]);
+    assert_eq!(select.to_string(), "SELECT t.id AS ident, name AS n, id + 1 FROM test_table AS t WHERE t.id > 1");
+}
+
+#[test]
+fn test_parse_compound_select() {
+    let statment = parse("SELECT DISTINCT id FROM a UNION ALL SELECT id FROM b EXCEPT SELECT id FROM c UNION SELECT id FROM d");
+    let Statement::Compound(compound) = statment else { panic!("expected a compound select") };
+    assert!(compound.first.distinct);
+    let ops: Vec<_> = compound.rest.iter().map(|(op, s)| (*op, s.table.as_str())).collect();
+    assert_eq!(ops, vec![(SetOperator::UnionAll, "b"), (SetOperator::Except, "c"), (SetOperator::Union, "d")]);
+    assert_eq!(compound.to_string(), "SELECT DISTINCT id FROM a UNION ALL SELECT id FROM b EXCEPT SELECT id FROM c UNION SELECT id FROM d");
+}
+
+#[test]
+fn test_parse_with() {
+    let statment = parse("WITH RECURSIVE cnt (n) AS (SELECT id FROM t UNION ALL SELECT n + 1 FROM cnt WHERE n < 3), other AS (SELECT n FROM cnt) SELECT n FROM other");
+    let Statement::With(with) = statment else { panic!("expected a with statment") };
+    assert!(with.recursive);
+    assert_eq!(with.ctes.len(), 2);
+    assert_eq!(with.ctes[0].columns, Some(vec!["n".to_string()]));
+    assert_eq!(with.ctes[0].query.rest[0].0, SetOperator::UnionAll);
+    assert_eq!(with.ctes[1].name, "other");
+    assert_eq!(with.body.first.table, "other");
+    assert_eq!(with.to_string(), "WITH RECURSIVE cnt (n) AS (SELECT id FROM t UNION ALL SELECT n + 1 FROM cnt WHERE n < 3), other AS (SELECT n FROM cnt) SELECT n FROM other");
+}
+
+#[test]
+fn test_parse_window() {
+    let statment = parse("SELECT rank() OVER (PARTITION BY name ORDER BY id DESC, name), lag(id, 1) OVER () FROM test_table");
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    let Expr::Window { name, args, partition_by, order_by, .. } = &select.columns[0].expr else { panic!("expected a window") };
+    assert_eq!(name, "RANK");
+    assert!(args.is_empty());
+    assert_eq!(partition_by, &vec![Expr::Identifier("name".to_string())]);
+    assert_eq!(order_by.iter().map(|o| o.descending).collect::<Vec<_>>(), vec![true, false]);
+    assert_eq!(select.columns[0].output_name(), "RANK() OVER (PARTITION BY name ORDER BY id DESC, name)");
+    assert_eq!(select.columns[1].output_name(), "LAG(id, 1) OVER ()");
+}
+
+#[test]
+fn test_parse_views() {
+    let statment = parse("CREATE VIEW recent AS SELECT id FROM test_table WHERE id > 2 UNION SELECT id FROM other_table");
+    let Statement::CreateView(view) = &statment else { panic!("expected a create view") };
+    assert_eq!(view.name, "recent");
+    assert!(matches!(*view.query, Statement::Compound(_)));
+    assert_eq!(statment.to_string(), "CREATE VIEW recent AS SELECT id FROM test_table WHERE id > 2 UNION SELECT id FROM other_table");
+    assert_eq!(parse("DROP VIEW recent"), Statement::DropView("recent".to_string()));
+}
+
+#[test]
+fn test_parse_materialized_views() {
+    let statment = parse("CREATE MATERIALIZED VIEW stats AS SELECT id FROM test_table");
+    let Statement::CreateView(view) = &statment else { panic!("expected a create view") };
+    assert!(view.materialized);
+    assert_eq!(statment.to_string(), "CREATE MATERIALIZED VIEW stats AS SELECT id FROM test_table");
+    assert_eq!(parse("REFRESH MATERIALIZED VIEW stats"), Statement::RefreshView("stats".to_string()));
+    assert_eq!(parse("DROP MATERIALIZED VIEW stats"), Statement::DropView("stats".to_string()));
+}
+
+#[test]
+fn test_parse_triggers() {
+    let sql_s = "CREATE TRIGGER log AFTER INSERT ON test_table FOR EACH ROW INSERT INTO audit VALUES (NEW.id, 'new')";
+    let statment = parse(sql_s);
+    let Statement::CreateTrigger(trigger) = &statment else { panic!("expected a create trigger") };
+    assert_eq!(trigger.timing, TriggerTiming::After);
+    assert_eq!(trigger.table, "test_table");
+    assert!(matches!(*trigger.body, Statement::Insert(_)));
+    assert_eq!(statment.to_string(), sql_s);
+
+    let statment = parse("CREATE TRIGGER check BEFORE INSERT ON test_table FOR EACH ROW SELECT id FROM audit WHERE id = NEW.id");
+    let Statement::CreateTrigger(trigger) = &statment else { panic!("expected a create trigger") };
+    assert_eq!(trigger.timing, TriggerTiming::Before);
+    assert!(matches!(*trigger.body, Statement::Select(_)));
+    assert_eq!(parse("DROP TRIGGER log"), Statement::DropTrigger("log".to_string()));
+}
+
+#[test]
+fn test_parse_lone_expression() {
+    let expr = sql::parser::parse_expr_sql("(price * qty)").unwrap();
+    assert_eq!(expr.to_string(), "price * qty");
+    assert!(sql::parser::parse_expr_sql("price qty").is_err());
+}
+
+#[test]
+fn test_parse_script() {
+    let script = sql::parser::parse_script("SELECT id FROM t;; INSERT INTO t VALUES (1);\nDROP VIEW v;").unwrap();
+    let statments: Vec<String> = script.into_iter().map(|root| {
+        let ASTNode::Statment(statment) = root.first_node;
+        statment.to_string()
+    }).collect();
+    assert_eq!(statments, ["SELECT id FROM t", "INSERT INTO t VALUES (1)", "DROP VIEW v"]);
+    assert!(sql::parser::parse_script("  ;  ").unwrap().is_empty());
+
+    let err = sql::parser::parse_script("SELECT id FROM t; SELECT FROM; SELECT id FROM t").unwrap_err();
+    assert_eq!(err.index(), Some(1));
+    // statments must be separated
+    let err = sql::parser::parse_script("SELECT id FROM t SELECT id FROM t").unwrap_err();
+    assert_eq!(err.index(), Some(0));
+}
+
+fn lex(sql_s: &str) -> Result<Vec<SqlToken>, String> {
+    let mut lx = sql::lexer::Lexer {
+        input: sql_s.chars().peekable(),
+    };
+    lx.lex()
+}
+
+#[test]
+fn test_lexing_comments_and_quotes() {
+    let tokens = lex("SELECT \"order\", \"full \"\"name\"\"\" -- trailing comment\nFROM /* block\n comment */ t WHERE x = 'it''s' - 1").unwrap();
+    let expected_vec = vec![
+        SqlToken::Keyword(SqlKeyword::Select),
+        SqlToken::Identifier("order".to_string()),
+        SqlToken::Comma,
+        SqlToken::Identifier("full \"name\"".to_string()),
+        SqlToken::Keyword(SqlKeyword::From),
+        SqlToken::Identifier("t".to_string()),
+        SqlToken::Keyword(SqlKeyword::Where),
+        SqlToken::Identifier("x".to_string()),
+        SqlToken::Operator(Operator::Equal),
+        SqlToken::StringLiteral("it's".to_string()),
+        SqlToken::Operator(Operator::Minus),
+        SqlToken::NumberLiteral("1".to_string()),
+        SqlToken::EOF,
+    ];
+    assert_eq!(tokens, expected_vec);
+    assert_eq!(lex("''''").unwrap()[0], SqlToken::StringLiteral("'".to_string()));
+
+    assert!(lex("SELECT 'open FROM t").is_err());
+    assert!(lex("SELECT \"open FROM t").is_err());
+    assert!(lex("SELECT \"\" FROM t").is_err());
+    assert!(lex("SELECT id FROM t /* open").is_err());
+}
+
+#[test]
+fn test_display_quotes_identifiers() {
+    let statment = parse("SELECT \"order\" AS \"full name\", id FROM \"select\" WHERE \"order\" = 'it''s'");
+    assert_eq!(statment.to_string(), "SELECT \"order\" AS \"full name\", id FROM \"select\" WHERE \"order\" = 'it''s'");
+    assert_eq!(parse(&statment.to_string()), statment);
+
+    // a quoted name holding a dot stays a single name, apart from a qualified one
+    let statment = parse("SELECT \"a.b\", t.\"c.d\" FROM t");
+    assert_eq!(statment.to_string(), "SELECT \"a.b\", t.\"c.d\" FROM t");
+    assert_eq!(parse(&statment.to_string()), statment);
+    let Statement::Select(select) = statment else { panic!("expected a select") };
+    assert_eq!(select.columns[0].expr, Expr::Identifier("a.b".to_string()));
+    assert_eq!(select.columns[1].expr, Expr::QualifiedIdentifier {qualifier: "t".to_string(), name: "c.d".to_string()});
+    assert_eq!(select.columns[1].output_name(), "c.d");
+}
+
+#[test]
+fn test_parse_placeholders() {
+    let statment = parse("SELECT name FROM t WHERE id = ? AND age > $5 OR name = ? OR city = :city_1");
+    assert_eq!(statment.to_string(), "SELECT name FROM t WHERE (((id = $1) AND (age > $5)) OR (name = $6)) OR (city = :city_1)");
+    assert_eq!(parse(&statment.to_string()), statment);
+    let prepared = crate::PreparedStatement::new("INSERT INTO t VALUES (:id, ?, :id)").unwrap();
+    assert_eq!(prepared.parameters(), [Parameter::Name("id".to_string()), Parameter::Index(1)]);
+
+    assert!(lex("SELECT $0 FROM t").is_err());
+    assert!(lex("SELECT $x FROM t").is_err());
+    assert!(lex("SELECT : FROM t").is_err());
+}
+
+#[test]
+fn test_parse_single_statment() {
+    assert!(sql::parser::parse_sql("SELECT id FROM t;;").is_ok());
+    assert!(sql::parser::parse_sql("SELECT id FROM t; SELECT id FROM t").is_err());
+    assert!(sql::parser::parse_sql("SELECT id FROM t WHERE id = 1 garbage").is_err());
+    assert!(sql::parser::parse_sql("SELECT id FROM t WHERE id = )").is_err());
+    assert!(sql::parser::parse_sql("SELECT id FROM t WHERE AND").is_err());
+}
+
+#[test]
+fn test_parse_header_columns() {
+    use crate::database::ColumnConstraint;
+    let column = sql::parser::parse_column_sql("TEXT NULL UNIQUE").unwrap();
+    assert_eq!((column.dt_type, column.nullable, column.constraint, column.generated), (DataTypes::TEXT, true, Some(ColumnConstraint::Unique), None));
+    let column = sql::parser::parse_column_sql("INT PRIMARY KEY GENERATED ALWAYS AS (LENGTH('VIRTUAL') + id) STORED").unwrap();
+    assert_eq!(column.constraint, Some(ColumnConstraint::PrimaryKey));
+    let generated = column.generated.unwrap();
+    assert!(generated.stored);
+    assert_eq!(generated.expr.to_string(), "LENGTH('VIRTUAL') + id");
+    // virtual is the default
+    assert!(!sql::parser::parse_column_sql("TEXT GENERATED ALWAYS AS (name)").unwrap().generated.unwrap().stored);
+
+    assert!(sql::parser::parse_column_sql("TEXT UNIQUE PRIMARY KEY").is_err());
+    assert!(sql::parser::parse_column_sql("TEXT UNIQUE NULL").is_err());
+    assert!(sql::parser::parse_column_sql("INT GENERATED ALWAYS AS id STORED").is_err());
+    assert!(sql::parser::parse_column_sql("INT GENERATED ALWAYS AS (id) STORED VIRTUAL").is_err());
+}
@@ -0,0 +1,32 @@
+use crate::sql::pattern::{glob_match, like_match};
+
+#[test]
+fn test_like_wildcards() {
+    assert!(like_match("Alice", "A%", None, false));
+    assert!(like_match("Alice", "%li%", None, false));
+    assert!(like_match("Alice", "_lic_", None, false));
+    assert!(!like_match("Alice", "_lic", None, false));
+    assert!(!like_match("Alice", "a%", None, false));
+    assert!(like_match("Alice", "a%E", None, true));
+    assert!(like_match("", "%", None, false));
+    assert!(like_match("aXbXc", "%b%c", None, false));
+}
+
+#[test]
+fn test_like_escape() {
+    assert!(like_match("100%", "100!%", Some('!'), false));
+    assert!(!like_match("1000", "100!%", Some('!'), false));
+    assert!(like_match("a_b", "a\\_b", Some('\\'), false));
+    assert!(!like_match("axb", "a\\_b", Some('\\'), false));
+}
+
+#[test]
+fn test_glob() {
+    assert!(glob_match("Alice", "A*"));
+    assert!(!glob_match("Alice", "a*"));
+    assert!(glob_match("Alice", "?lic?"));
+    assert!(glob_match("Bob", "[A-C]*"));
+    assert!(!glob_match("Rob", "[A-C]*"));
+    assert!(glob_match("Rob", "[^A-C]ob"));
+    assert!(!glob_match("Rob", "[A-C"));
+}
@@ -0,0 +1,30 @@
+use std::cmp::Ordering;
+use crate::database::DBField;
+use crate::sql::window::{order_fields, partitions, peer_groups, sort_partition};
+
+#[test]
+fn test_order_fields() {
+    assert_eq!(order_fields(&DBField::Int(2), &DBField::Int(10)), Ordering::Less);
+    assert_eq!(order_fields(&DBField::Text("b".to_string()), &DBField::Text("a".to_string())), Ordering::Greater);
+    assert_eq!(order_fields(&DBField::Null, &DBField::Int(-5)), Ordering::Less);
+    assert_eq!(order_fields(&DBField::Text("1".to_string()), &DBField::Int(2)), Ordering::Greater);
+}
+
+#[test]
+fn test_partitions_and_peers() {
+    let int = |i| vec![DBField::Int(i)];
+    let parts = partitions(&[int(1), int(0), int(1), int(0), int(2)]);
+    assert_eq!(parts, vec![vec![0, 2], vec![1, 3], vec![4]]);
+
+    let keys = vec![int(3), int(1), int(3), int(2)];
+    let mut part = vec![0, 1, 2, 3];
+    sort_partition(&mut part, &keys, &[false]);
+    assert_eq!(part, vec![1, 3, 0, 2]);
+    assert_eq!(peer_groups(&part, &keys), vec![0..1, 1..2, 2..4]);
+    sort_partition(&mut part, &keys, &[true]);
+    assert_eq!(part, vec![0, 2, 3, 1]);
+
+    let unordered = vec![vec![]; 3];
+    assert_eq!(peer_groups(&[0, 1, 2], &unordered), vec![0..3]);
+    assert!(peer_groups(&[], &unordered).is_empty());
+}